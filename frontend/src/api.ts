@@ -44,7 +44,9 @@ export type VersionView = {
   homepage?: string
   repository?: string
   license?: string
+  checksum?: string
   keywords: string[]
+  authors: string[]
   owners: UserView[]
   created_at: string
 }
@@ -0,0 +1,6 @@
+DELETE FROM dependencies WHERE kind <> 'normal';
+
+ALTER TABLE dependencies DROP CONSTRAINT unique_version_dependency;
+ALTER TABLE dependencies ADD CONSTRAINT unique_version_dependency UNIQUE (version_id, package_id);
+
+ALTER TABLE dependencies DROP COLUMN kind;
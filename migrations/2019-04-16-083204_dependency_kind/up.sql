@@ -0,0 +1,4 @@
+ALTER TABLE dependencies ADD COLUMN kind VARCHAR NOT NULL DEFAULT 'normal';
+
+ALTER TABLE dependencies DROP CONSTRAINT unique_version_dependency;
+ALTER TABLE dependencies ADD CONSTRAINT unique_version_dependency UNIQUE (version_id, package_id, kind);
@@ -0,0 +1,7 @@
+DELETE FROM dependencies WHERE package_id IS NULL;
+
+ALTER TABLE dependencies DROP CONSTRAINT local_or_foreign_dependency;
+ALTER TABLE dependencies DROP COLUMN foreign_name;
+ALTER TABLE dependencies DROP COLUMN foreign_group;
+ALTER TABLE dependencies DROP COLUMN foreign_index;
+ALTER TABLE dependencies ALTER COLUMN package_id SET NOT NULL;
@@ -0,0 +1,10 @@
+ALTER TABLE dependencies ALTER COLUMN package_id DROP NOT NULL;
+ALTER TABLE dependencies ADD COLUMN foreign_index VARCHAR;
+ALTER TABLE dependencies ADD COLUMN foreign_group VARCHAR;
+ALTER TABLE dependencies ADD COLUMN foreign_name VARCHAR;
+
+ALTER TABLE dependencies ADD CONSTRAINT local_or_foreign_dependency CHECK (
+    (package_id IS NOT NULL AND foreign_index IS NULL)
+    OR (package_id IS NULL AND foreign_index IS NOT NULL
+        AND foreign_group IS NOT NULL AND foreign_name IS NOT NULL)
+);
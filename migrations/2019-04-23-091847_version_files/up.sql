@@ -0,0 +1,10 @@
+CREATE TABLE version_files
+(
+    id SERIAL PRIMARY KEY,
+    version_id INTEGER NOT NULL REFERENCES versions (id),
+    path VARCHAR NOT NULL,
+    size BIGINT NOT NULL,
+    mode INTEGER NOT NULL,
+
+    CONSTRAINT unique_version_file UNIQUE (version_id, path)
+);
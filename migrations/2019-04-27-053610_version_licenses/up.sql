@@ -0,0 +1,10 @@
+CREATE TABLE version_licenses
+(
+    id SERIAL PRIMARY KEY,
+    version_id INTEGER NOT NULL REFERENCES versions (id),
+    license VARCHAR NOT NULL,
+
+    CONSTRAINT unique_version_license UNIQUE (version_id, license)
+);
+
+CREATE INDEX version_licenses_license ON version_licenses (license);
@@ -0,0 +1,16 @@
+CREATE TABLE outbox_events
+(
+    id SERIAL PRIMARY KEY,
+    version_id INTEGER NOT NULL REFERENCES versions (id),
+    kind VARCHAR NOT NULL,
+    payload JSONB,
+    tarball_path VARCHAR,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error VARCHAR,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT now(),
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    processed_at TIMESTAMP
+);
+
+CREATE INDEX outbox_events_unfinished ON outbox_events (id) WHERE status <> 'done';
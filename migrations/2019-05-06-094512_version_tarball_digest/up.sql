@@ -0,0 +1,3 @@
+-- sha256 of the tarball if it is stored in the content-addressed layout,
+-- NULL if it is stored under the path derived from the package version
+ALTER TABLE versions ADD COLUMN tarball_digest VARCHAR;
@@ -0,0 +1,2 @@
+-- the worker only looks at pending events that are due
+CREATE INDEX outbox_events_due ON outbox_events (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,7 @@
+-- whether a readme was stored along with the tarball on publish
+ALTER TABLE versions ADD COLUMN has_readme BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- versions published before are assumed to have stored one if they ship it
+UPDATE versions SET has_readme = TRUE WHERE id IN (
+    SELECT version_id FROM version_files WHERE path ~* '(^|/)readme(\.[^/]*)?$'
+);
@@ -0,0 +1,456 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use elba::package::manifest::Manifest;
+use failure::{Error, ResultExt};
+use flate2::read::GzDecoder;
+use tar::{Archive, Entry};
+
+use crate::model::packages::PackageFile;
+use crate::util::error::Reason;
+
+// Upper bound of the unpacked archive size, in multiples of the max upload
+// size of the group.
+const MAX_UNPACK_RATIO: u64 = 10;
+const MAX_README_SIZE: u64 = 2 * 1024 * 1024;
+
+/// A condition that does not fail the publish but that the publisher
+/// should know about.
+#[derive(Serialize, Clone)]
+pub struct PublishWarning {
+    pub warning: &'static str,
+    pub description: String,
+}
+
+impl PublishWarning {
+    pub fn new(warning: &'static str, description: String) -> Self {
+        PublishWarning {
+            warning,
+            description,
+        }
+    }
+}
+
+/// What is read from a package tarball.
+pub struct PackageArchive {
+    pub manifest: Manifest,
+    pub manifest_file: String,
+    pub readme: Option<String>,
+    // path of the readme in the archive
+    pub readme_path: Option<PathBuf>,
+    pub files: Vec<PackageFile>,
+    pub warnings: Vec<PublishWarning>,
+}
+
+/// Walks through the tarball once, verifying every entry and picking
+/// out the manifest and the readme along the way. `max_upload_size` is the
+/// limit that applies to the group of the package.
+pub fn read_archive(tarball: &mut File, max_upload_size: usize) -> Result<PackageArchive, Error> {
+    tarball.seek(SeekFrom::Start(0))?;
+
+    let mut archive = Archive::new(GzDecoder::new(&*tarball));
+    let mut visited = HashSet::new();
+    let mut unpack_size = 0u64;
+    let max_unpack_size = max_upload_size as u64 * MAX_UNPACK_RATIO;
+
+    let mut manifest: Option<(Manifest, String)> = None;
+    let mut declared_readme = None;
+    // whether the declared readme was met after the manifest, even if it
+    // was too large to keep
+    let mut declared_readme_seen = false;
+    let mut fallback_readme: Option<(PathBuf, Option<String>)> = None;
+    // the declared readme may be the fallback one too, so a readme that is
+    // too large is only reported once at the end
+    let mut too_large = BTreeSet::new();
+    let mut files = Vec::new();
+    let mut warnings = Vec::new();
+
+    for entry in archive
+        .entries()
+        .with_context(|_| human!(Reason::InvalidArchive, "Package is not a valid tarball"))?
+    {
+        let mut entry = entry.with_context(|err| {
+            human!(Reason::InvalidArchive, "Failed to read archive entry: {}", err)
+        })?;
+
+        let path = verify_entry(&entry, &mut visited, &mut unpack_size, max_unpack_size)?;
+
+        if entry.header().entry_type().is_file() {
+            files.push(PackageFile {
+                path: path.to_string_lossy().into_owned(),
+                size: entry.header().entry_size()?,
+                mode: entry.header().mode()?,
+            });
+        }
+
+        if path.to_string_lossy().to_lowercase() == "elba.toml" {
+            let mut buffer = String::new();
+            entry.read_to_string(&mut buffer)?;
+            manifest = Some((Manifest::from_str(&buffer)?, buffer));
+            continue;
+        }
+
+        let is_declared_readme = match &manifest {
+            Some((manifest, _)) => manifest
+                .package
+                .readme
+                .as_ref()
+                .map_or(false, |subpath| subpath.0 == path),
+            None => false,
+        };
+        let is_fallback_readme = fallback_readme.is_none() && match path.file_stem() {
+            Some(file_stem) => file_stem.to_string_lossy().to_uppercase() == "README",
+            None => false,
+        };
+
+        if is_declared_readme || is_fallback_readme {
+            let content = read_readme(&mut entry)?;
+            if is_declared_readme {
+                declared_readme_seen = true;
+                if content.is_none() {
+                    too_large.insert(path.clone());
+                }
+                declared_readme = content;
+            } else {
+                fallback_readme = Some((path, content));
+            }
+        }
+    }
+
+    let (manifest, manifest_file) =
+        manifest.ok_or_else(|| human!(Reason::InvalidManifest, "Manifest not found in archive"))?;
+
+    // The declared readme can only be recognized once the manifest is read,
+    // so it takes another pass if it precedes `elba.toml` in the archive.
+    if !declared_readme_seen {
+        if let Some(subpath) = &manifest.package.readme {
+            if visited.contains(&subpath.0) {
+                declared_readme = find_readme(tarball, &subpath.0)?;
+                if declared_readme.is_none() {
+                    too_large.insert(subpath.0.clone());
+                }
+            } else {
+                warnings.push(PublishWarning::new(
+                    "readme_not_found",
+                    format!(
+                        "Readme `{}` declared in manifest is not in the archive",
+                        subpath.0.display()
+                    ),
+                ));
+            }
+        }
+    }
+
+    let (readme, readme_path) = match (declared_readme, fallback_readme) {
+        (Some(readme), _) => (
+            Some(readme),
+            manifest.package.readme.as_ref().map(|subpath| subpath.0.clone()),
+        ),
+        (None, Some((path, Some(readme)))) => {
+            warnings.push(PublishWarning::new(
+                "fallback_readme",
+                format!(
+                    "Using `{}` as the readme since no declared readme is available",
+                    path.display()
+                ),
+            ));
+            (Some(readme), Some(path))
+        }
+        (None, Some((path, None))) => {
+            too_large.insert(path);
+            (None, None)
+        }
+        (None, None) => (None, None),
+    };
+    warnings.extend(too_large.iter().map(|path| readme_too_large(path)));
+
+    Ok(PackageArchive {
+        manifest,
+        manifest_file,
+        readme,
+        readme_path,
+        files,
+        warnings,
+    })
+}
+
+/// Rejects entries that could escape or corrupt the unpack directory, and
+/// returns the normalized path of the entry.
+fn verify_entry<R: Read>(
+    entry: &Entry<R>,
+    visited: &mut HashSet<PathBuf>,
+    unpack_size: &mut u64,
+    max_unpack_size: u64,
+) -> Result<PathBuf, Error> {
+    let path = entry
+        .path()
+        .with_context(|err| human!(Reason::InvalidArchive, "Invalid path in archive: {}", err))?;
+    let display = path.to_string_lossy().into_owned();
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(human!(
+                    Reason::InvalidArchive,
+                    "Archive entry `{}` escapes the package root with `..`",
+                    &display
+                ))
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(human!(
+                    Reason::InvalidArchive,
+                    "Archive entry `{}` has an absolute path",
+                    &display
+                ))
+            }
+        }
+    }
+
+    let entry_type = entry.header().entry_type();
+    if entry_type.is_symlink() || entry_type.is_hard_link() {
+        return Err(human!(
+            Reason::InvalidArchive,
+            "Archive entry `{}` is a link, which is not allowed",
+            &display
+        ));
+    }
+    if !entry_type.is_file() && !entry_type.is_dir() {
+        return Err(human!(
+            Reason::InvalidArchive,
+            "Archive entry `{}` is not a regular file or directory",
+            &display
+        ));
+    }
+
+    if !visited.insert(normalized.clone()) {
+        return Err(human!(
+            Reason::InvalidArchive,
+            "Archive entry `{}` appears more than once",
+            &display
+        ));
+    }
+
+    // Checked before the entry body is decompressed, so gzip bombs are
+    // rejected without inflating them.
+    *unpack_size += entry.header().entry_size()?;
+    if *unpack_size > max_unpack_size {
+        return Err(human!(
+            Reason::InvalidArchive,
+            "Archive unpacks to more than {} bytes (exceeded at entry `{}`)",
+            max_unpack_size,
+            &display
+        ));
+    }
+
+    Ok(normalized)
+}
+
+fn find_readme(tarball: &mut File, subpath: &Path) -> Result<Option<String>, Error> {
+    tarball.seek(SeekFrom::Start(0))?;
+
+    let mut archive = Archive::new(GzDecoder::new(&*tarball));
+    let entry = archive
+        .entries()?
+        .filter_map(Result::ok)
+        .find(|entry| match entry.path() {
+            Ok(path) => path == subpath,
+            Err(_) => false,
+        });
+
+    match entry {
+        Some(mut entry) => read_readme(&mut entry),
+        None => Ok(None),
+    }
+}
+
+/// Reads the readme entry, or returns `None` if it is too large to keep.
+fn read_readme<R: Read>(entry: &mut Entry<R>) -> Result<Option<String>, Error> {
+    if entry.header().entry_size()? > MAX_README_SIZE {
+        return Ok(None);
+    }
+
+    let mut buffer = String::new();
+    entry.read_to_string(&mut buffer)?;
+
+    Ok(Some(buffer))
+}
+
+fn readme_too_large(path: &Path) -> PublishWarning {
+    PublishWarning::new(
+        "readme_too_large",
+        format!(
+            "Readme `{}` is over {} bytes and was dropped",
+            path.display(),
+            MAX_README_SIZE
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, EntryType, Header};
+
+    use super::*;
+
+    const MANIFEST: &[u8] = br#"
+[package]
+name = "group/package"
+version = "1.0.0"
+authors = ["author"]
+
+[targets.lib]
+path = "src/"
+mods = ["Package"]
+"#;
+
+    struct TestEntry {
+        path: &'static str,
+        entry_type: EntryType,
+        data: Vec<u8>,
+    }
+
+    fn file(path: &'static str, data: &[u8]) -> TestEntry {
+        TestEntry {
+            path,
+            entry_type: EntryType::Regular,
+            data: data.to_vec(),
+        }
+    }
+
+    fn special(path: &'static str, entry_type: EntryType) -> TestEntry {
+        TestEntry {
+            path,
+            entry_type,
+            data: Vec::new(),
+        }
+    }
+
+    fn tarball(entries: Vec<TestEntry>) -> File {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+        for entry in entries {
+            let mut header = Header::new_gnu();
+            // written by hand, since `set_path` refuses the paths under test
+            header.as_mut_bytes()[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+            header.set_entry_type(entry.entry_type);
+            header.set_size(entry.data.len() as u64);
+            header.set_mode(0o644);
+            if entry.entry_type.is_symlink() || entry.entry_type.is_hard_link() {
+                header.set_link_name("elba.toml").unwrap();
+            }
+            header.set_cksum();
+            builder.append(&header, &entry.data[..]).unwrap();
+        }
+
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&bytes).unwrap();
+        file
+    }
+
+    fn rejection(entries: Vec<TestEntry>, max_upload_size: usize) -> String {
+        match read_archive(&mut tarball(entries), max_upload_size) {
+            Ok(_) => panic!("archive was accepted"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn accepts_plain_archive() {
+        let archive = read_archive(
+            &mut tarball(vec![
+                special("src", EntryType::Directory),
+                file("./elba.toml", MANIFEST),
+                file("src/Package.idr", b"module Package"),
+                file("README.md", b"# Package"),
+            ]),
+            1024 * 1024,
+        ).unwrap_or_else(|err| panic!("archive was rejected: {}", err));
+
+        assert_eq!(archive.files.len(), 3);
+        assert_eq!(archive.readme.as_ref().unwrap(), "# Package");
+    }
+
+    #[test]
+    fn reports_large_readme_once() {
+        let manifest = String::from_utf8(MANIFEST.to_vec())
+            .unwrap()
+            .replace("version = \"1.0.0\"", "version = \"1.0.0\"\nreadme = \"README.md\"");
+        let readme = vec![b'#'; MAX_README_SIZE as usize + 1];
+
+        // the declared readme precedes the manifest and is a fallback readme too
+        let archive = read_archive(
+            &mut tarball(vec![
+                file("README.md", &readme),
+                file("elba.toml", manifest.as_bytes()),
+            ]),
+            1024 * 1024,
+        ).unwrap_or_else(|err| panic!("archive was rejected: {}", err));
+
+        assert!(archive.readme.is_none());
+        let warnings: Vec<&str> = archive.warnings.iter().map(|w| w.warning).collect();
+        assert_eq!(warnings, vec!["readme_too_large"]);
+    }
+
+    #[test]
+    fn rejects_parent_components() {
+        let err = rejection(vec![file("src/../../evil", b"")], 1024);
+        assert!(err.contains("escapes the package root"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let err = rejection(vec![file("/etc/evil", b"")], 1024);
+        assert!(err.contains("absolute path"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_links() {
+        let err = rejection(vec![special("src/link", EntryType::Symlink)], 1024);
+        assert!(err.contains("is a link"), "{}", err);
+
+        let err = rejection(vec![special("src/link", EntryType::Link)], 1024);
+        assert!(err.contains("is a link"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_device_files() {
+        let err = rejection(vec![special("src/device", EntryType::Char)], 1024);
+        assert!(err.contains("not a regular file"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_duplicate_entries() {
+        let err = rejection(
+            vec![file("src/Package.idr", b""), file("./src/Package.idr", b"")],
+            1024,
+        );
+        assert!(err.contains("more than once"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_archives_over_unpack_limit() {
+        let max_upload_size = 16;
+        let limit = max_upload_size as u64 * MAX_UNPACK_RATIO;
+
+        let err = rejection(
+            vec![
+                file("a", &vec![0; limit as usize / 2]),
+                file("b", &vec![0; limit as usize / 2 + 1]),
+            ],
+            max_upload_size,
+        );
+        assert!(err.contains(&format!("more than {} bytes", limit)), "{}", err);
+        assert!(err.contains("entry `b`"), "{}", err);
+    }
+}
@@ -1,25 +1,49 @@
 use std::env;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use elba::remote::resolution::IndexRes;
 use elba::remote::Registry;
+use rusoto_core::Region;
 
 use crate::login::GhOAuthConfig;
-use crate::storage::StorageConfig;
+use crate::model::packages::GroupName;
+use crate::policy::PublishPolicy;
+use crate::storage::{CacheConfig, RetryPolicy, StorageConfig};
+use crate::util::license;
 
 #[derive(Clone)]
 pub struct Config {
     pub bind_to: String,
     pub max_upload_size: usize,
     pub storage_config: StorageConfig,
+    // target of `migrate-storage`
+    pub migration_storage_config: Option<StorageConfig>,
+    pub storage_threads: usize,
+    // threads uploading published packages, apart from `storage_threads`
+    pub storage_upload_threads: usize,
+    // local disk cache in front of the storage, `None` if disabled
+    pub storage_cache: Option<CacheConfig>,
+    // whether new tarballs are keyed by their sha256
+    pub content_addressed_tarballs: bool,
+    // whether content-addressed tarballs are also kept at their old paths
+    pub legacy_tarball_paths: bool,
+    pub storage_retry: RetryPolicy,
+    // where uploaded tarballs wait for the outbox worker to store them
+    pub outbox_staging_dir: PathBuf,
     pub registry: Registry,
     pub remote_index_url: String,
     pub remote_index_user: Option<String>,
     pub remote_index_pwd: Option<String>,
+    pub foreign_indices: Vec<IndexRes>,
     pub index_bot_name: String,
     pub index_bot_email: String,
     pub cors_origin: Option<String>,
     pub gh_oauth_config: Option<GhOAuthConfig>,
+    pub publish_policy: PublishPolicy,
+    // github names of users who can access admin endpoints
+    pub admin_users: Vec<String>,
 }
 
 impl Config {
@@ -37,30 +61,41 @@ impl Config {
             None
         };
 
-        let storage_config = match read_env("STORAGE_STRATEGY").to_uppercase().as_str() {
-            "LOCAL" => StorageConfig::Local {
-                path: read_env_path("STORAGE_LOCAL_PATH"),
-                url: read_env("STORAGE_LOCAL_URL"),
-            },
-            "S3" => StorageConfig::S3 {
-                bucket: read_env("STORAGE_S3_BUCKET"),
-                access_key: read_env("STORAGE_S3_ACCESS_KEY"),
-                secret_key: read_env("STORAGE_S3_SECRET_KEY"),
-                base_url: read_env("STORAGE_S3_BASE_URL"),
-                region: read_env("STORAGE_S3_REGION")
-                    .parse()
-                    .expect("STORAGE_S3_REGION is not a valid AWS region"),
-            },
-            _ => panic!("`STORAGE_STRATEGY` only accepts `LOCAL` or `S3`."),
-        };
-
         Config {
             bind_to: read_env("BIND_TO"),
             max_upload_size: read_env("MAX_UPLOAD_SIZE")
                 .parse()
                 .expect("MAX_UPLOAD_SIZE is expected to be number."),
             gh_oauth_config,
-            storage_config,
+            storage_config: read_storage_config(""),
+            migration_storage_config: read_optional("MIGRATION_STORAGE_STRATEGY")
+                .map(|_| read_storage_config("MIGRATION_")),
+            storage_threads: read_optional("STORAGE_THREADS")
+                .map(|threads| {
+                    threads
+                        .parse()
+                        .expect("STORAGE_THREADS is expected to be number.")
+                }).unwrap_or_else(|| num_cpus::get() * 2),
+            storage_upload_threads: read_optional("STORAGE_UPLOAD_THREADS")
+                .map(|threads| {
+                    threads
+                        .parse()
+                        .expect("STORAGE_UPLOAD_THREADS is expected to be number.")
+                }).unwrap_or_else(num_cpus::get),
+            storage_retry: read_storage_retry(),
+            storage_cache: read_optional("STORAGE_CACHE_DIR").map(|dir| CacheConfig {
+                dir: PathBuf::from(dir),
+                max_size: read_optional("STORAGE_CACHE_SIZE")
+                    .map(|size| {
+                        size.parse()
+                            .expect("STORAGE_CACHE_SIZE is expected to be number.")
+                    }).unwrap_or(1024 * 1024 * 1024),
+            }),
+            content_addressed_tarballs: read_flag("STORAGE_CONTENT_ADDRESSED"),
+            legacy_tarball_paths: read_flag("STORAGE_LEGACY_TARBALL_PATHS"),
+            outbox_staging_dir: read_optional("OUTBOX_STAGING_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("./tmp/outbox")),
             registry: Registry {
                 url: read_env("REGISTRY_URL")
                     .parse()
@@ -69,13 +104,192 @@ impl Config {
             remote_index_url: read_env("REMOTE_INDEX_URL"),
             remote_index_user: read_optional("REMOTE_INDEX_USER"),
             remote_index_pwd: read_optional("REMOTE_INDEX_PWD"),
+            foreign_indices: read_optional("FOREIGN_INDICES")
+                .map(|indices| {
+                    split_list(&indices)
+                        .map(|index| {
+                            index
+                                .parse()
+                                .expect("FOREIGN_INDICES contains an invalid index.")
+                        }).collect()
+                }).unwrap_or_default(),
             index_bot_name: read_env("INDEX_BOT_NAME"),
             index_bot_email: read_env("INDEX_BOT_EMAIL"),
             cors_origin: read_optional("CORS_ORIGIN"),
+            publish_policy: read_publish_policy(),
+            admin_users: read_optional("ADMIN_USERS")
+                .map(|users| split_list(&users).map(|user| user.to_owned()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn read_publish_policy() -> PublishPolicy {
+    let default = PublishPolicy::default();
+
+    PublishPolicy {
+        max_description_len: read_optional("POLICY_MAX_DESCRIPTION_LENGTH")
+            .map(|len| {
+                len.parse()
+                    .expect("POLICY_MAX_DESCRIPTION_LENGTH is expected to be number.")
+            }).unwrap_or(default.max_description_len),
+        max_keywords: read_optional("POLICY_MAX_KEYWORDS")
+            .map(|max| {
+                max.parse()
+                    .expect("POLICY_MAX_KEYWORDS is expected to be number.")
+            }).unwrap_or(default.max_keywords),
+        require_license: read_flag("POLICY_REQUIRE_LICENSE"),
+        require_repository: read_flag("POLICY_REQUIRE_REPOSITORY"),
+        require_readme: read_flag("POLICY_REQUIRE_README"),
+        allowed_licenses: read_optional("POLICY_ALLOWED_LICENSES").map(|licenses| {
+            split_list(&licenses)
+                .map(|id| {
+                    if license::is_license_ref(id) {
+                        return id.to_owned();
+                    }
+                    license::canonical_id(id)
+                        .expect("POLICY_ALLOWED_LICENSES contains an unknown license.")
+                        .to_owned()
+                }).collect()
+        }),
+        group_max_upload_size: read_optional("POLICY_GROUP_MAX_UPLOAD_SIZE")
+            .map(|sizes| {
+                split_list(&sizes)
+                    .map(|entry| {
+                        let mut parts = entry.splitn(2, '=');
+                        let group = parts.next().map(normalize_group);
+                        let size = parts.next().and_then(|size| size.trim().parse().ok());
+                        match (group, size) {
+                            (Some(group), Some(size)) => (group, size),
+                            _ => panic!(
+                                "POLICY_GROUP_MAX_UPLOAD_SIZE expects entries like `group=size`."
+                            ),
+                        }
+                    }).collect()
+            }).unwrap_or_default(),
+        no_prerelease_groups: read_optional("POLICY_NO_PRERELEASE_GROUPS")
+            .map(|groups| split_list(&groups).map(normalize_group).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Reads the storage config from `STORAGE_*` vars, prefixed with `prefix`.
+fn read_storage_config(prefix: &str) -> StorageConfig {
+    let var = |name: &str| format!("{}STORAGE_{}", prefix, name);
+
+    match read_env(&var("STRATEGY")).to_uppercase().as_str() {
+        "LOCAL" => StorageConfig::Local {
+            path: read_env_path(&var("LOCAL_PATH")),
+            url: read_optional(&var("LOCAL_URL")),
+        },
+        "S3" => {
+            let bucket = read_env(&var("S3_BUCKET"));
+            let (region, base_url) = s3_location(
+                prefix,
+                &bucket,
+                read_optional(&var("S3_ENDPOINT")),
+                read_optional(&var("S3_REGION")),
+                read_optional(&var("S3_BASE_URL")),
+            );
+
+            StorageConfig::S3 {
+                bucket,
+                access_key: read_env(&var("S3_ACCESS_KEY")),
+                secret_key: read_env(&var("S3_SECRET_KEY")),
+                base_url,
+                region,
+                cache_control: read_optional(&var("S3_CACHE_CONTROL")),
+                timeout: Duration::from_secs(
+                    read_optional(&var("S3_TIMEOUT"))
+                        .map(|secs| {
+                            secs.parse().expect(&format!(
+                                "{} is expected to be number.",
+                                var("S3_TIMEOUT")
+                            ))
+                        }).unwrap_or(30),
+                ),
+            }
         }
+        _ => panic!("`{}` only accepts `LOCAL` or `S3`.", var("STRATEGY")),
+    }
+}
+
+/// The region and the public url of an S3 bucket, given the optional
+/// `STORAGE_S3_ENDPOINT`, `STORAGE_S3_REGION` and `STORAGE_S3_BASE_URL`.
+fn s3_location(
+    prefix: &str,
+    bucket: &str,
+    endpoint: Option<String>,
+    region: Option<String>,
+    base_url: Option<String>,
+) -> (Region, String) {
+    let var = |name: &str| format!("{}STORAGE_{}", prefix, name);
+    let endpoint = endpoint.map(|endpoint| endpoint.trim_end_matches('/').to_owned());
+
+    // S3-compatible stores take any region name
+    let region = match (&endpoint, region) {
+        (Some(endpoint), region) => Region::Custom {
+            name: region.unwrap_or_else(|| "us-east-1".to_owned()),
+            endpoint: endpoint.clone(),
+        },
+        (None, Some(region)) => region
+            .parse()
+            .expect(&format!("{} is not a valid AWS region", var("S3_REGION"))),
+        (None, None) => panic!("Environment variable `{}` not set.", var("S3_REGION")),
+    };
+
+    // objects are addressed in path style
+    let base_url = match (base_url, &endpoint) {
+        (Some(base_url), _) => base_url,
+        (None, Some(endpoint)) => format!("{}/{}", endpoint, bucket),
+        (None, None) => panic!("Environment variable `{}` not set.", var("S3_BASE_URL")),
+    };
+
+    (region, base_url)
+}
+
+fn read_storage_retry() -> RetryPolicy {
+    let default = RetryPolicy::default();
+
+    RetryPolicy {
+        max_retries: read_optional("STORAGE_MAX_RETRIES")
+            .map(|retries| {
+                retries
+                    .parse()
+                    .expect("STORAGE_MAX_RETRIES is expected to be number.")
+            }).unwrap_or(default.max_retries),
+        base_backoff: read_optional("STORAGE_RETRY_BACKOFF")
+            .map(|millis| {
+                Duration::from_millis(
+                    millis
+                        .parse()
+                        .expect("STORAGE_RETRY_BACKOFF is expected to be number."),
+                )
+            }).unwrap_or(default.base_backoff),
+        max_backoff: read_optional("STORAGE_RETRY_MAX_BACKOFF")
+            .map(|millis| {
+                Duration::from_millis(
+                    millis
+                        .parse()
+                        .expect("STORAGE_RETRY_MAX_BACKOFF is expected to be number."),
+                )
+            }).unwrap_or(default.max_backoff),
     }
 }
 
+fn split_list<'a>(list: &'a str) -> impl Iterator<Item = &'a str> {
+    list.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+}
+
+fn normalize_group(group: &str) -> String {
+    GroupName::new(group.trim().to_owned())
+        .expect("Policy contains an invalid group name.")
+        .normalized_group()
+        .to_owned()
+}
+
 fn read_env(env_name: &str) -> String {
     env::var(env_name).expect(&format!("Environment variable `{}` not set.", env_name))
 }
@@ -84,8 +298,86 @@ fn read_optional(env_name: &str) -> Option<String> {
     env::var(env_name).ok().filter(|var| !var.is_empty())
 }
 
+fn read_flag(env_name: &str) -> bool {
+    read_optional(env_name)
+        .map(|flag| {
+            flag.parse()
+                .expect(&format!("`{}` should be a boolean", env_name))
+        }).unwrap_or(false)
+}
+
 fn read_env_path(env_name: &str) -> PathBuf {
     let path = PathBuf::from(read_env(env_name));
     fs::create_dir_all(&path).expect(&format!("Can not create dir `{:?}`", &path));
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(value: &str) -> Option<String> {
+        Some(value.to_owned())
+    }
+
+    #[test]
+    fn s3_on_aws() {
+        let (region, base_url) = s3_location(
+            "",
+            "bucket",
+            None,
+            some("eu-west-1"),
+            some("https://bucket.s3.amazonaws.com"),
+        );
+
+        assert_eq!(region, Region::EuWest1);
+        assert_eq!(base_url, "https://bucket.s3.amazonaws.com");
+    }
+
+    #[test]
+    #[should_panic(expected = "STORAGE_S3_REGION")]
+    fn s3_on_aws_requires_region() {
+        s3_location("", "bucket", None, None, some("https://example.com"));
+    }
+
+    #[test]
+    #[should_panic(expected = "MIGRATION_STORAGE_S3_BASE_URL")]
+    fn s3_on_aws_requires_base_url() {
+        s3_location("MIGRATION_", "bucket", None, some("us-east-1"), None);
+    }
+
+    #[test]
+    fn s3_compatible_endpoint() {
+        let (region, base_url) =
+            s3_location("", "bucket", some("http://localhost:9000/"), None, None);
+
+        assert_eq!(
+            region,
+            Region::Custom {
+                name: "us-east-1".to_owned(),
+                endpoint: "http://localhost:9000".to_owned(),
+            }
+        );
+        assert_eq!(base_url, "http://localhost:9000/bucket");
+    }
+
+    #[test]
+    fn s3_compatible_endpoint_with_overrides() {
+        let (region, base_url) = s3_location(
+            "",
+            "bucket",
+            some("http://minio:9000"),
+            some("home"),
+            some("https://cdn.example.com"),
+        );
+
+        assert_eq!(
+            region,
+            Region::Custom {
+                name: "home".to_owned(),
+                endpoint: "http://minio:9000".to_owned(),
+            }
+        );
+        assert_eq!(base_url, "https://cdn.example.com");
+    }
+}
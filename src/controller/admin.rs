@@ -0,0 +1,66 @@
+use actix_web::*;
+use failure::Error;
+use tokio_async_await::await;
+
+use crate::controller::auth::AdminUser;
+use crate::model::consistency::{CheckStorage, StorageReport};
+use crate::storage::metrics::{CacheSnapshot, OperationSnapshot, METRICS};
+use crate::AppState;
+
+#[derive(Deserialize, Clone)]
+pub struct CheckStorageReq {
+    #[serde(default)]
+    pub delete_orphans: bool,
+    #[serde(default)]
+    pub restore_readmes: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StorageReportView {
+    pub missing: Vec<String>,
+    pub orphans: Vec<String>,
+    pub deleted: Vec<String>,
+    pub restored: Vec<String>,
+}
+
+pub async fn check_storage(
+    (query, state, _): (Query<CheckStorageReq>, State<AppState>, AdminUser),
+) -> Result<HttpResponse, Error> {
+    let report = await!(state.db.send(CheckStorage {
+        delete_orphans: query.delete_orphans,
+        restore_readmes: query.restore_readmes,
+    }))??;
+
+    #[derive(Serialize)]
+    struct R {
+        report: StorageReportView,
+    }
+
+    Ok(HttpResponse::Ok().json(R {
+        report: report.into(),
+    }))
+}
+
+pub async fn storage_metrics(_: AdminUser) -> Result<HttpResponse, Error> {
+    #[derive(Serialize)]
+    struct R {
+        operations: Vec<OperationSnapshot>,
+        cache: CacheSnapshot,
+    }
+
+    Ok(HttpResponse::Ok().json(R {
+        operations: METRICS.snapshot(),
+        cache: METRICS.cache_snapshot(),
+    }))
+}
+
+impl From<StorageReport> for StorageReportView {
+    fn from(report: StorageReport) -> StorageReportView {
+        StorageReportView {
+            missing: report.missing,
+            orphans: report.orphans,
+            deleted: report.deleted,
+            restored: report.restored,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use actix_web::error::InternalError;
+use actix_web::http::header;
+use actix_web::middleware::identity::RequestIdentity;
+use actix_web::{FromRequest, FutureResponse, HttpRequest};
+use failure::Error;
+use futures::{future, Future};
+use tokio_async_await::await;
+
+use crate::model::users::{LookupUserByToken, User};
+use crate::util::async_await::compat_future;
+use crate::util::error::{report_error, Reason};
+use crate::{AppState, CONFIG};
+
+/// The user who sends the request with an access token.
+///
+/// The token is read from `Authorization: Bearer <token>`. The `token` query
+/// parameter is still accepted but deprecated, because query strings end up
+/// in access logs.
+pub struct TokenUser(pub User);
+
+/// The user who is logged in with the session cookie, or who sends the
+/// request with an access token.
+pub struct SessionUser {
+    pub user_id: i32,
+}
+
+/// A token user who is listed in `ADMIN_USERS`.
+pub struct AdminUser(pub User);
+
+impl FromRequest<AppState> for TokenUser {
+    type Config = ();
+    type Result = FutureResponse<Self>;
+
+    fn from_request(req: &HttpRequest<AppState>, _: &Self::Config) -> Self::Result {
+        let token = read_token(req);
+        let db = req.state().db.clone();
+
+        report_rejection(compat_future(
+            async move {
+                let access_token =
+                    token?.ok_or_else(|| human!(Reason::NoPermission, "please login first"))?;
+                let user = await!(db.send(LookupUserByToken { access_token }))??;
+
+                Ok(TokenUser(user))
+            },
+        ))
+    }
+}
+
+impl FromRequest<AppState> for SessionUser {
+    type Config = ();
+    type Result = FutureResponse<Self>;
+
+    fn from_request(req: &HttpRequest<AppState>, _: &Self::Config) -> Self::Result {
+        if let Some(user_id) = req.identity() {
+            if let Ok(user_id) = user_id.parse() {
+                return Box::new(future::ok(SessionUser { user_id }));
+            }
+        }
+
+        Box::new(
+            TokenUser::from_request(req, &()).map(|TokenUser(user)| SessionUser {
+                user_id: user.id,
+            }),
+        )
+    }
+}
+
+impl FromRequest<AppState> for AdminUser {
+    type Config = ();
+    type Result = FutureResponse<Self>;
+
+    fn from_request(req: &HttpRequest<AppState>, _: &Self::Config) -> Self::Result {
+        Box::new(
+            TokenUser::from_request(req, &()).and_then(|TokenUser(user)| {
+                if CONFIG.admin_users.contains(&user.gh_name) {
+                    Ok(AdminUser(user))
+                } else {
+                    let err = human!(Reason::NoPermission, "only admins can do this");
+                    Err(InternalError::from_response("", report_error(err)).into())
+                }
+            }),
+        )
+    }
+}
+
+fn read_token(req: &HttpRequest<AppState>) -> Result<Option<String>, Error> {
+    if let Some(authorization) = req.headers().get(header::AUTHORIZATION) {
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|authorization| {
+                let mut parts = authorization.splitn(2, ' ');
+                match (parts.next(), parts.next()) {
+                    // auth schemes are case-insensitive
+                    (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                        Some(token.trim().to_owned())
+                    }
+                    _ => None,
+                }
+            }).ok_or_else(|| {
+                human!(
+                    Reason::InvalidRequest,
+                    "Authorization header should be in the form of `Bearer <token>`"
+                )
+            })?;
+
+        return Ok(Some(token));
+    }
+
+    match req.query().get("token") {
+        Some(token) => {
+            warn!("Access token is passed in query string, which is deprecated");
+            Ok(Some(token.to_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+fn report_rejection<T: 'static>(future: FutureResponse<T, Error>) -> FutureResponse<T> {
+    Box::new(future.map_err(|err| InternalError::from_response("", report_error(err)).into()))
+}
@@ -1,2 +1,5 @@
+pub mod admin;
+pub mod auth;
 pub mod packages;
+pub mod storage;
 pub mod users;
@@ -15,11 +15,24 @@ pub async fn download(
 ) -> Result<HttpResponse, Error> {
     let package_version = PackageVersion::try_from(path.into_inner())?;
 
+    let (package_version, version) =
+        await!(state.db.send(LookupVersion(package_version.clone())))??;
+
     state.db.do_send(IncreaseDownload(package_version.clone()));
 
-    Ok(HttpResponse::TemporaryRedirect()
-        .header("Location", storage::get_tarball_location(&package_version))
-        .finish())
+    let mut response = HttpResponse::TemporaryRedirect();
+    response.header(
+        "Location",
+        storage::get_tarball_location(
+            &package_version,
+            version.tarball_digest.as_ref().map(|s| s.as_str()),
+        ),
+    );
+    if let Some(checksum) = version.checksum {
+        response.header("X-Checksum-Sha256", checksum);
+    }
+
+    Ok(response.finish())
 }
 
 pub async fn download_stats(
@@ -0,0 +1,97 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path};
+use std::str;
+
+use actix_web::*;
+use failure::Error;
+use flate2::read::GzDecoder;
+use tar::Archive;
+use tokio_async_await::await;
+
+use crate::model::packages::*;
+use crate::storage::FetchTarball;
+use crate::AppState;
+
+use super::*;
+
+pub async fn list_files(
+    (path, state): (actix_web::Path<PackageVersionReq>, State<AppState>),
+) -> Result<HttpResponse, Error> {
+    let package_version = PackageVersion::try_from(path.into_inner())?;
+
+    let files = await!(state.db.send(ListFiles(package_version)))??;
+    let files = files.into_iter().map(FileView::from).collect();
+
+    #[derive(Serialize)]
+    struct R {
+        files: Vec<FileView>,
+    }
+
+    Ok(HttpResponse::Ok().json(R { files }))
+}
+
+pub async fn show_file(
+    (path, state): (actix_web::Path<PackageFileReq>, State<AppState>),
+) -> Result<HttpResponse, Error> {
+    let PackageFileReq {
+        group,
+        package,
+        version,
+        path,
+    } = path.into_inner();
+    let package_version = PackageVersion::try_from(PackageVersionReq {
+        group,
+        package,
+        version,
+    })?;
+
+    let file = await!(state.db.send(LookupFile {
+        package: package_version.clone(),
+        path,
+    }))??;
+
+    let (package_version, version) = await!(state.db.send(LookupVersion(package_version)))??;
+    let mut tarball = await!(state.storage.send(FetchTarball {
+        package: package_version,
+        digest: version.tarball_digest,
+    }))??;
+
+    let content = read_file(&mut tarball, Path::new(&file.path))?
+        .ok_or_else(|| format_err!("file `{}` is missing in tarball", &file.path))?;
+
+    // Never let browsers render the source as a page of the registry.
+    let content_type = match str::from_utf8(&content) {
+        Ok(_) => "text/plain; charset=utf-8",
+        Err(_) => "application/octet-stream",
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .header("X-Content-Type-Options", "nosniff")
+        .body(content))
+}
+
+fn read_file(tarball: &mut File, subpath: &Path) -> Result<Option<Vec<u8>>, Error> {
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    let entry = archive
+        .entries()?
+        .filter_map(Result::ok)
+        .find(|entry| match entry.path() {
+            Ok(path) => path
+                .components()
+                .filter(|component| *component != Component::CurDir)
+                .eq(subpath.components()),
+            Err(_) => false,
+        });
+
+    match entry {
+        Some(mut entry) => {
+            let mut buffer = Vec::new();
+            entry.read_to_end(&mut buffer)?;
+            Ok(Some(buffer))
+        }
+        None => Ok(None),
+    }
+}
@@ -6,8 +6,11 @@ use failure::Error;
 use tokio_async_await::await;
 
 use crate::controller::users::UserView;
+use crate::model::outbox::ListSideEffects;
 use crate::model::packages::*;
 use crate::storage;
+use crate::util::error::Reason;
+use crate::util::license;
 use crate::AppState;
 
 use super::*;
@@ -57,6 +60,57 @@ pub async fn list_versions(
     Ok(HttpResponse::Ok().json(R { versions }))
 }
 
+/// Lists the versions an author is credited in. The author is given by
+/// name, e.g. `Jane Doe`, optionally followed by an email as in manifests.
+pub async fn list_author_versions(
+    (path, state): (Path<AuthorReq>, State<AppState>),
+) -> Result<HttpResponse, Error> {
+    let mut versions = await!(state.db.send(ListVersionsByAuthor {
+        name: path.into_inner().name
+    }))??;
+    versions.sort_by(|lhs, rhs| {
+        (lhs.name.as_str(), &rhs.semver).cmp(&(rhs.name.as_str(), &lhs.semver))
+    });
+
+    let versions = versions.into_iter().map(PackageVersionReq::from).collect();
+
+    #[derive(Serialize)]
+    struct R {
+        versions: Vec<PackageVersionReq>,
+    }
+
+    Ok(HttpResponse::Ok().json(R { versions }))
+}
+
+pub async fn list_license_packages(
+    (path, state): (Path<LicenseReq>, State<AppState>),
+) -> Result<HttpResponse, Error> {
+    let license = match license::canonical_id(&path.license) {
+        Some(license) => license,
+        None => {
+            return Err(human!(
+                Reason::InvalidRequest,
+                "Unknown license `{}`, expected an SPDX license identifier",
+                &path.license
+            ))
+        }
+    };
+
+    let mut packages = await!(state.db.send(ListPackagesByLicense {
+        license: license.to_owned()
+    }))??;
+    packages.sort_by(|lhs, rhs| lhs.as_str().cmp(rhs.as_str()));
+
+    let packages = packages.into_iter().map(PackageReq::from).collect();
+
+    #[derive(Serialize)]
+    struct R {
+        packages: Vec<PackageReq>,
+    }
+
+    Ok(HttpResponse::Ok().json(R { packages }))
+}
+
 pub async fn list_dependencies(
     (path, state): (Path<PackageVersionReq>, State<AppState>),
 ) -> Result<HttpResponse, Error> {
@@ -113,6 +167,10 @@ pub async fn show_package(
         version_id: latest_version.id,
     }))??;
 
+    let authors = await!(state.db.send(ListAuthors {
+        version_id: latest_version.id,
+    }))??;
+
     let latest_version_meta = VersionView {
         package_version: package_version.into(),
         yanked: latest_version.yanked,
@@ -120,7 +178,9 @@ pub async fn show_package(
         homepage: latest_version.homepage,
         repository: latest_version.repository,
         license: latest_version.license,
+        checksum: latest_version.checksum,
         keywords,
+        authors,
         owners,
         created_at: latest_version.created_at,
     };
@@ -154,6 +214,10 @@ pub async fn show_version(
         version_id: version.id,
     }))??;
 
+    let authors = await!(state.db.send(ListAuthors {
+        version_id: version.id,
+    }))??;
+
     let owners = await!(state.db.send(ListOwners(package_version.name.clone())))??;
     let owners = owners.into_iter().map(UserView::from).collect();
 
@@ -164,7 +228,9 @@ pub async fn show_version(
         homepage: version.homepage,
         repository: version.repository,
         license: version.license,
+        checksum: version.checksum,
         keywords,
+        authors,
         owners,
         created_at: version.created_at,
     };
@@ -179,10 +245,50 @@ pub async fn show_version(
     }))
 }
 
-pub async fn show_readme(path: Path<PackageVersionReq>) -> Result<HttpResponse, Error> {
+pub async fn show_readme(
+    (path, query): (Path<PackageVersionReq>, Query<ReadmeReq>),
+) -> Result<HttpResponse, Error> {
+    let package_version = PackageVersion::try_from(path.clone())?;
+
+    let location = match query.format.as_ref().map(|s| s.as_str()) {
+        None | Some("markdown") => storage::get_readme_location(&package_version),
+        Some("html") => storage::get_readme_html_location(&package_version),
+        Some(format) => {
+            return Err(human!(
+                Reason::InvalidRequest,
+                "Unknown readme format `{}`, expected `markdown` or `html`",
+                format
+            ))
+        }
+    };
+
+    Ok(HttpResponse::TemporaryRedirect()
+        .header("Location", location)
+        .finish())
+}
+
+pub async fn list_side_effects(
+    (path, state): (Path<PackageVersionReq>, State<AppState>),
+) -> Result<HttpResponse, Error> {
+    let package_version = PackageVersion::try_from(path.clone())?;
+    let side_effects = await!(state.db.send(ListSideEffects(package_version)))??;
+    let side_effects = side_effects
+        .into_iter()
+        .map(SideEffectView::from)
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        side_effects: Vec<SideEffectView>,
+    }
+
+    Ok(HttpResponse::Ok().json(R { side_effects }))
+}
+
+pub async fn show_manifest(path: Path<PackageVersionReq>) -> Result<HttpResponse, Error> {
     let package_version = PackageVersion::try_from(path.clone())?;
 
     Ok(HttpResponse::TemporaryRedirect()
-        .header("Location", storage::get_readme_location(&package_version))
+        .header("Location", storage::get_manifest_location(&package_version))
         .finish())
 }
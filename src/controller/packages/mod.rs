@@ -1,4 +1,5 @@
 pub mod download;
+pub mod files;
 pub mod metadata;
 
 mod publish;
@@ -17,6 +18,7 @@ use failure::{Error, ResultExt};
 use semver;
 
 use crate::controller::users::UserView;
+use crate::model::outbox::{EventStatus, SideEffect, SideEffectReport};
 use crate::model::packages::*;
 use crate::util::error::Reason;
 
@@ -38,6 +40,29 @@ pub struct PackageVersionReq {
     pub version: String,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct PackageFileReq {
+    pub group: String,
+    pub package: String,
+    pub version: String,
+    pub path: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ReadmeReq {
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LicenseReq {
+    pub license: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AuthorReq {
+    pub name: String,
+}
+
 #[derive(Serialize, Clone)]
 pub struct GroupView {
     #[serde(flatten)]
@@ -66,7 +91,9 @@ pub struct VersionView {
     pub homepage: Option<String>,
     pub repository: Option<String>,
     pub license: Option<String>,
+    pub checksum: Option<String>,
     pub keywords: Vec<String>,
+    pub authors: Vec<String>,
     pub owners: Vec<UserView>,
     #[serde(with = "crate::util::rfc3339")]
     pub created_at: NaiveDateTime,
@@ -77,6 +104,37 @@ pub struct DependencyView {
     #[serde(flatten)]
     pub package: PackageReq,
     pub version_req: String,
+    pub kind: DependencyKind,
+    pub index: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PublishReportView {
+    #[serde(flatten)]
+    pub package_version: PackageVersionReq,
+    pub new_group: bool,
+    pub new_package: bool,
+    pub owners: Vec<UserView>,
+    pub dependencies: Vec<DependencyView>,
+    pub no_op: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FileView {
+    pub path: String,
+    pub size: u64,
+    pub mode: u32,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SideEffectView {
+    pub kind: SideEffect,
+    pub status: EventStatus,
+    pub attempts: u32,
+    #[serde(with = "crate::util::rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "crate::util::rfc3339::option")]
+    pub processed_at: Option<NaiveDateTime>,
 }
 
 #[derive(Serialize, Clone)]
@@ -165,6 +223,8 @@ impl From<DependencyReq> for DependencyView {
         DependencyView {
             package: dependency.name.into(),
             version_req: dependency.version_req.to_string(),
+            kind: dependency.kind,
+            index: dependency.index.map(|index| index.to_string()),
         }
     }
 }
@@ -177,3 +237,25 @@ impl From<GlobalStats> for GlobalStatsView {
         }
     }
 }
+
+impl From<SideEffectReport> for SideEffectView {
+    fn from(report: SideEffectReport) -> SideEffectView {
+        SideEffectView {
+            kind: report.kind,
+            status: report.status,
+            attempts: report.attempts as u32,
+            created_at: report.created_at,
+            processed_at: report.processed_at,
+        }
+    }
+}
+
+impl From<PackageFile> for FileView {
+    fn from(file: PackageFile) -> FileView {
+        FileView {
+            path: file.path,
+            size: file.size,
+            mode: file.mode,
+        }
+    }
+}
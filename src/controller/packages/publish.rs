@@ -1,112 +1,173 @@
 use std::convert::TryFrom;
-use std::io::Read;
-use std::path::Path;
-use std::str::FromStr;
 
 use actix_web::*;
 use elba::package::manifest::{DepReq, Manifest};
 use failure::Error;
-use flate2::read::GzDecoder;
-use tar::Archive;
+use futures::{future, Future, Stream};
+use sha2::{Digest, Sha256};
 use tokio_async_await::await;
 
+use crate::archive::{read_archive, PackageArchive, PublishWarning};
+use crate::controller::auth::TokenUser;
+use crate::controller::users::UserView;
 use crate::model::packages::*;
-use crate::util::empty_response;
+use crate::policy::PublishCandidate;
+use crate::storage::WriteChunk;
 use crate::util::error::Reason;
+use crate::util::license;
+use crate::util::markdown;
 use crate::{AppState, CONFIG};
 
-use super::PackageVersionReq;
+use super::{DependencyView, PackageVersionReq, PublishReportView};
 
 #[derive(Deserialize, Clone)]
 pub struct PublishReq {
-    pub token: String,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 pub async fn publish(
-    (path, query, state, req): (
+    (path, query, state, req, user): (
         actix_web::Path<PackageVersionReq>,
         Query<PublishReq>,
         State<AppState>,
         HttpRequest<AppState>,
+        TokenUser,
     ),
 ) -> Result<HttpResponse, Error> {
     let package_version = PackageVersion::try_from(path.into_inner())?;
 
     info!("Receiving tarball");
 
-    let body = await!(req.body().limit(CONFIG.max_upload_size))?;
+    let max_upload_size = CONFIG
+        .publish_policy
+        .max_upload_size(package_version.name.normalized_group(), CONFIG.max_upload_size);
 
-    let manifest = read_manifest(&body)?;
+    let storage = state.storage.clone();
+    let (mut tarball, hasher, tarball_size) = await!(req.payload().from_err::<Error>().fold(
+        (tempfile::tempfile()?, Sha256::default(), 0usize),
+        move |(tarball, mut hasher, size), chunk| {
+            let size = size + chunk.len();
+            if size > max_upload_size {
+                return future::Either::A(future::err(human!(
+                    Reason::InvalidRequest,
+                    "Package is over {} bytes",
+                    max_upload_size
+                )));
+            }
+
+            hasher.input(&chunk);
+
+            // writing blocks, so it is done by the storage threads
+            future::Either::B(
+                storage
+                    .send(WriteChunk {
+                        file: tarball,
+                        chunk,
+                    }).from_err::<Error>()
+                    .and_then(|result| result)
+                    .map(move |tarball| (tarball, hasher, size)),
+            )
+        }
+    ))?;
+    let checksum = format!("{:x}", hasher.result());
+
+    let PackageArchive {
+        manifest,
+        manifest_file,
+        readme,
+        readme_path,
+        files,
+        mut warnings,
+    } = read_archive(&mut tarball, max_upload_size)?;
     verify_manifest(&package_version, &manifest)?;
 
+    if manifest.package.description.is_none() {
+        warnings.push(PublishWarning::new(
+            "missing_description",
+            "Manifest has no description".to_owned(),
+        ));
+    }
+    if manifest.package.license.is_none() {
+        warnings.push(PublishWarning::new(
+            "missing_license",
+            "Manifest has no license".to_owned(),
+        ));
+    }
+
+    let license = match &manifest.package.license {
+        Some(expression) => Some(license::parse_license(expression)?),
+        None => None,
+    };
+
+    CONFIG.publish_policy.check(&PublishCandidate {
+        package: &manifest.package,
+        license: license.as_ref(),
+        has_readme: readme.is_some(),
+        tarball_size,
+    })?;
+
     let deps = deps_in_manifest(&manifest)?;
-    let readme_file = read_readme(
-        &body,
-        manifest
-            .package
-            .readme
-            .as_ref()
-            .map(|subpath| subpath.0.as_path()),
-    )?;
-
-    await!(state.db.send(PublishVersion {
+
+    let readme_html = match (&readme, &readme_path) {
+        (Some(readme), Some(readme_path)) => Some(markdown::render_readme(
+            readme,
+            manifest.package.repository.as_ref().map(|s| s.as_str()),
+            readme_path,
+        )),
+        _ => None,
+    };
+
+    let report = await!(state.db.send(PublishVersion {
         package_info: manifest.package.clone(),
-        readme_file,
+        license,
+        manifest_file,
+        readme_file: readme,
+        readme_html,
+        files,
         dependencies: deps.clone(),
-        token: query.token.clone(),
-        bytes: body,
+        user_id: user.0.id,
+        tarball,
+        checksum,
+        dry_run: query.dry_run,
     }))??;
 
-    Ok(empty_response())
-}
-
-fn read_manifest(bytes: &[u8]) -> Result<Manifest, Error> {
-    let mut archive = Archive::new(GzDecoder::new(bytes));
-    let mut entry = archive
-        .entries()?
-        .filter_map(Result::ok)
-        .find(|entry| match entry.path() {
-            Ok(ref path) if path.to_string_lossy().to_lowercase() == "elba.toml" => true,
-            _ => false,
-        }).ok_or_else(|| human!(Reason::InvalidManifest, "Manifest not found in archive"))?;
-
-    let mut buffer = String::new();
-    entry.read_to_string(&mut buffer)?;
-    let manifest = Manifest::from_str(&buffer)?;
-
-    Ok(manifest)
-}
-
-fn read_readme(bytes: &[u8], subpath: Option<&Path>) -> Result<Option<String>, Error> {
-    let mut archive = Archive::new(GzDecoder::new(bytes));
-    let entry = archive.entries()?.filter_map(Result::ok).find(|entry| {
-        if let Ok(path) = entry.path() {
-            if let Some(subpath) = subpath {
-                if path == subpath {
-                    return true;
-                }
-            }
-            if let Some(file_stem) = path.file_stem() {
-                return &file_stem.to_string_lossy().to_uppercase() == "README";
-            }
-            false
-        } else {
-            false
-        }
-    });
+    if query.dry_run {
+        let report = PublishReportView {
+            package_version: package_version.into(),
+            new_group: report.new_group,
+            new_package: report.new_package,
+            owners: report.owners.into_iter().map(UserView::from).collect(),
+            dependencies: report
+                .dependencies
+                .into_iter()
+                .map(DependencyView::from)
+                .collect(),
+            no_op: report.no_op,
+        };
 
-    if let Some(mut entry) = entry {
-        if entry.header().entry_size()? > 2 * 1024 * 1024 {
-            return Ok(None);
+        #[derive(Serialize)]
+        struct R {
+            dry_run: PublishReportView,
+            warnings: Vec<PublishWarning>,
         }
 
-        let mut buffer = String::new();
-        entry.read_to_string(&mut buffer)?;
+        return Ok(HttpResponse::Ok().json(R {
+            dry_run: report,
+            warnings,
+        }));
+    }
 
-        Ok(Some(buffer))
-    } else {
-        Ok(None)
+    #[derive(Serialize)]
+    struct R {
+        no_op: bool,
+        warnings: Vec<PublishWarning>,
     }
+
+    Ok(HttpResponse::Ok().json(R {
+        no_op: report.no_op,
+        warnings,
+    }))
 }
 
 fn verify_manifest(req: &PackageVersion, manifest: &Manifest) -> Result<(), Error> {
@@ -128,31 +189,6 @@ fn verify_manifest(req: &PackageVersion, manifest: &Manifest) -> Result<(), Erro
         ));
     }
 
-    if let Some(description) = &manifest.package.description {
-        if description.len() > 244 {
-            return Err(human!(
-                Reason::InvalidManifest,
-                "Descrption is over 244 characters"
-            ));
-        }
-    }
-
-    if let Some(license) = &manifest.package.license {
-        if license.len() > 10 {
-            return Err(human!(
-                Reason::InvalidManifest,
-                "License is over ten characters"
-            ));
-        }
-    }
-
-    if manifest.package.keywords.len() > 5 {
-        return Err(human!(
-            Reason::InvalidManifest,
-            "No more than five keywords"
-        ));
-    }
-
     if manifest
         .package
         .keywords
@@ -183,9 +219,38 @@ fn verify_manifest(req: &PackageVersion, manifest: &Manifest) -> Result<(), Erro
 fn deps_in_manifest(manifest: &Manifest) -> Result<Vec<(DependencyReq)>, Error> {
     let mut deps = Vec::new();
 
-    for (name, ver_req) in manifest.dependencies.iter() {
-        let version_req = match ver_req {
-            DepReq::Registry(constrain) => constrain.clone(),
+    let dependencies = manifest
+        .dependencies
+        .iter()
+        .map(|dep| (dep, DependencyKind::Normal));
+    let dev_dependencies = manifest
+        .dev_dependencies
+        .iter()
+        .map(|dep| (dep, DependencyKind::Dev));
+
+    for ((name, ver_req), kind) in dependencies.chain(dev_dependencies) {
+        let (version_req, index) = match ver_req {
+            DepReq::Registry(constrain) => (constrain.clone(), None),
+            // Dev dependencies are only needed to test the package itself,
+            // so those that can not be resolved through an index are left
+            // out instead of failing the publish.
+            DepReq::RegLong { registry, .. }
+                if kind == DependencyKind::Dev && !CONFIG.foreign_indices.contains(registry) =>
+            {
+                continue
+            }
+            DepReq::RegLong { con, registry } => {
+                if !CONFIG.foreign_indices.contains(registry) {
+                    return Err(human!(
+                        Reason::InvalidManifest,
+                        "Dependency {} is hosted in index `{}`, which is not allowed by this registry",
+                        name.as_str(),
+                        registry
+                    ));
+                }
+                (con.clone(), Some(registry.clone()))
+            }
+            _ if kind == DependencyKind::Dev => continue,
             _ => {
                 return Err(human!(
                     Reason::InvalidManifest,
@@ -198,6 +263,8 @@ fn deps_in_manifest(manifest: &Manifest) -> Result<Vec<(DependencyReq)>, Error>
         deps.push(DependencyReq {
             name: name.clone(),
             version_req,
+            kind,
+            index,
         });
     }
 
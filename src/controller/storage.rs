@@ -0,0 +1,62 @@
+use std::io;
+
+use actix_web::fs::NamedFile;
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use actix_web::*;
+use failure::Error;
+
+use crate::storage::{self, StorageConfig};
+use crate::{AppState, CONFIG};
+
+// Stored objects never change once a version is published.
+const CACHE_CONTROL_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+#[derive(Deserialize, Clone)]
+pub struct StorageObjectReq {
+    pub dir: String,
+    pub file: String,
+}
+
+/// Serves objects from `STORAGE_LOCAL_PATH` when no external url is
+/// configured to serve them.
+pub async fn serve_object(
+    (path, req): (Path<StorageObjectReq>, HttpRequest<AppState>),
+) -> Result<HttpResponse, Error> {
+    let root = match &CONFIG.storage_config {
+        StorageConfig::Local {
+            path: root,
+            url: None,
+        } => root,
+        _ => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    if path.file.starts_with('.') || path.file.contains('/') || path.file.contains('\\') {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let file = match NamedFile::open(root.join(&path.dir).join(&path.file)) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    // conditional and range requests are handled by `NamedFile`
+    let mut response = file
+        .use_etag(true)
+        .use_last_modified(true)
+        .respond_to(&req)?;
+
+    let headers = response.headers_mut();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_IMMUTABLE));
+    // not modified responses carry no body to describe
+    if headers.contains_key(CONTENT_TYPE) {
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(storage::content_type(&path.file)),
+        );
+    }
+
+    Ok(response)
+}
@@ -1,8 +1,8 @@
-use actix_web::middleware::identity::RequestIdentity;
 use actix_web::*;
 use failure::Error;
 use tokio_async_await::await;
 
+use crate::controller::auth::SessionUser;
 use crate::model::users::*;
 use crate::AppState;
 
@@ -24,15 +24,15 @@ pub async fn show_user(
 }
 
 pub async fn show_user_self(
-    (state, req): (State<AppState>, HttpRequest<AppState>),
+    (state, user): (State<AppState>, Option<SessionUser>),
 ) -> Result<HttpResponse, Error> {
     #[derive(Serialize)]
     struct R {
         user: Option<UserView>,
     }
 
-    let user_id: i32 = match req.identity() {
-        Some(user_id) => user_id.parse().unwrap(),
+    let user_id = match user {
+        Some(user) => user.user_id,
         None => {
             return Ok(HttpResponse::Ok().json(R { user: None }));
         }
@@ -17,6 +17,8 @@ pub type Pool = diesel::r2d2::Pool<ConnectionManager<PgConnection>>;
 pub struct Database {
     pub index: Addr<Index>,
     pub storage: Addr<Storage>,
+    // storage threads reserved for uploading published packages
+    pub uploads: Addr<Storage>,
     pub search: Addr<Search>,
     pub pool: Pool,
 }
@@ -9,7 +9,7 @@ use failure::{Error, ResultExt};
 use itertools::Itertools;
 use serde_json;
 
-use crate::model::packages::{DependencyReq, PackageVersion};
+use crate::model::packages::{DependencyKind, DependencyReq, PackageVersion};
 use crate::CONFIG;
 
 use self::repo::IndexRepo;
@@ -33,6 +33,7 @@ impl Actor for Index {
 pub struct UpdatePackage {
     pub package: PackageVersion,
     pub dependencies: Vec<(DependencyReq)>,
+    pub checksum: String,
 }
 
 pub struct YankPackage {
@@ -40,6 +41,20 @@ pub struct YankPackage {
     pub yanked: bool,
 }
 
+/// A line in the index metafile. Registry specific fields are stored
+/// along with the fields of `TomlEntry`, which elba clients understand.
+/// Dev dependencies are kept apart, since clients resolve only
+/// `dependencies` when the package is depended on.
+#[derive(Serialize, Deserialize)]
+pub struct IndexEntry {
+    #[serde(flatten)]
+    pub entry: TomlEntry,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dev_dependencies: Vec<TomlDep>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
 impl Message for UpdatePackage {
     type Result = Result<(), Error>;
 }
@@ -79,15 +94,24 @@ impl Handler<UpdatePackage> for Index {
         let mut entries = parse_entries(&content);
 
         // fix potential violation
-        entries
-            .retain(|entry| entry.name != msg.package.name || entry.version != msg.package.semver);
+        entries.retain(|entry| {
+            entry.entry.name != msg.package.name || entry.entry.version != msg.package.semver
+        });
 
         // insert metadata entry
         let mut metadata = TomlEntry::from(msg.package.clone());
+        let mut dev_dependencies = Vec::new();
         for dep in msg.dependencies {
-            metadata.dependencies.push(TomlDep::from(dep));
+            match dep.kind {
+                DependencyKind::Normal => metadata.dependencies.push(TomlDep::from(dep)),
+                DependencyKind::Dev => dev_dependencies.push(TomlDep::from(dep)),
+            }
         }
-        entries.push(metadata);
+        entries.push(IndexEntry {
+            entry: metadata,
+            dev_dependencies,
+            checksum: Some(msg.checksum),
+        });
 
         // save entries
         let mut file = OpenOptions::new()
@@ -142,7 +166,7 @@ impl Handler<YankPackage> for Index {
         let mut entries = parse_entries(&content);
 
         // modify metadata entry
-        for entry in &mut entries {
+        for IndexEntry { entry, .. } in &mut entries {
             if entry.name == msg.package.name && entry.version == msg.package.semver {
                 entry.yanked = msg.yanked;
             }
@@ -171,14 +195,14 @@ impl Handler<YankPackage> for Index {
     }
 }
 
-fn parse_entries(content: &str) -> Vec<TomlEntry> {
+fn parse_entries(content: &str) -> Vec<IndexEntry> {
     content
         .split("\n")
         .filter_map(|line| serde_json::from_str(line).ok())
         .collect()
 }
 
-fn serialize_entries(entries: Vec<TomlEntry>) -> String {
+fn serialize_entries(entries: Vec<IndexEntry>) -> String {
     entries
         .iter()
         .filter_map(|entry| serde_json::to_string(entry).ok())
@@ -205,7 +229,7 @@ impl From<DependencyReq> for TomlDep {
     fn from(req: DependencyReq) -> Self {
         TomlDep {
             name: req.name,
-            index: None,
+            index: req.index,
             req: req.version_req,
         }
     }
@@ -4,12 +4,15 @@
 
 #[macro_use]
 mod util;
+mod archive;
 mod config;
 mod controller;
 mod database;
 mod index;
 mod login;
 mod model;
+mod outbox;
+mod policy;
 mod router;
 mod schema;
 mod search;
@@ -36,7 +39,9 @@ use crate::config::Config;
 use crate::database::Database;
 use crate::index::Index;
 use crate::login::GhLogin;
+use crate::model::consistency::CheckStorage;
 use crate::model::packages::PopulateSearch;
+use crate::outbox::{OutboxProcessor, OutboxWorker};
 use crate::search::Search;
 use crate::storage::Storage;
 
@@ -48,6 +53,7 @@ lazy_static! {
 pub struct AppState {
     pub login: Addr<GhLogin>,
     pub search: Addr<Search>,
+    pub storage: Addr<Storage>,
     pub db: Addr<Database>,
 }
 
@@ -56,25 +62,113 @@ fn main() {
 
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `migrate-storage [--journal <path>]` copies every object to the
+    // storage configured by `MIGRATION_STORAGE_*` and exits.
+    if args.first().map(|arg| arg.as_str()) == Some("migrate-storage") {
+        let target = CONFIG
+            .migration_storage_config
+            .as_ref()
+            .expect("`MIGRATION_STORAGE_STRATEGY` not set.");
+        let journal = args
+            .iter()
+            .position(|arg| arg == "--journal")
+            .and_then(|index| args.get(index + 1))
+            .map(|path| path.as_str())
+            .unwrap_or("./storage-migration.log");
+
+        let source = storage::open_backend(&CONFIG.storage_config)
+            .expect("faild to initialize storage");
+        let target = storage::open_backend(target).expect("faild to initialize target storage");
+
+        let report = storage::migrate(
+            &*source,
+            &*target,
+            &CONFIG.storage_retry,
+            std::path::Path::new(journal),
+        ).expect("failed to migrate storage");
+
+        println!(
+            "copied {}, skipped {} done before, removed {} gone from the source, failed {}",
+            report.copied,
+            report.skipped,
+            report.removed.len(),
+            report.failed.len()
+        );
+        for path in report.failed.iter() {
+            println!("    {}", path);
+        }
+        if !report.failed.is_empty() {
+            println!("run the migration again to retry the failed objects");
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let mut sys = System::new("elba-registry");
 
     let index = Index::new().expect("faild to initialize index").start();
-    let storage = Storage::new().expect("faild to initialize storage").start();
+    let storage = Storage::new().expect("faild to initialize storage");
+    let uploads = {
+        let storage = storage.clone();
+        SyncArbiter::start(CONFIG.storage_upload_threads, move || storage.clone())
+    };
+    let storage = SyncArbiter::start(CONFIG.storage_threads, move || storage.clone());
     let search = Search::new().start();
 
     let db_pool = database::connect();
 
-    let db = Database {
+    let database = Database {
         index,
-        storage,
+        storage: storage.clone(),
+        uploads,
         search: search.clone(),
         pool: db_pool,
     };
-    let db = SyncArbiter::start(num_cpus::get() * 4, move || db.clone());
+    let db = {
+        let database = database.clone();
+        SyncArbiter::start(num_cpus::get() * 4, move || database.clone())
+    };
 
-    let login = GhLogin::new(db.clone()).start();
+    // `check-storage [--delete-orphans] [--restore-readmes]` checks the
+    // storage against the database and exits without serving.
+    if args.first().map(|arg| arg.as_str()) == Some("check-storage") {
+        let report = sys
+            .block_on(db.send(CheckStorage {
+                delete_orphans: args.iter().any(|arg| arg == "--delete-orphans"),
+                restore_readmes: args.iter().any(|arg| arg == "--restore-readmes"),
+            })).unwrap()
+            .expect("failed to check storage");
+
+        for (title, paths) in &[
+            ("missing", &report.missing),
+            ("orphans", &report.orphans),
+            ("deleted", &report.deleted),
+            ("restored", &report.restored),
+        ] {
+            println!("{} ({}):", title, paths.len());
+            for path in paths.iter() {
+                println!("    {}", path);
+            }
+        }
+
+        return;
+    }
 
-    let app_state = AppState { db, search, login };
+    let login = GhLogin::new(db.clone()).start();
+    let outbox = SyncArbiter::start(1, move || OutboxProcessor {
+        db: database.clone(),
+    });
+    OutboxWorker::new(outbox).start();
+
+    let app_state = AppState {
+        db,
+        search,
+        storage,
+        login,
+    };
 
     sys.block_on(app_state.db.send(PopulateSearch))
         .unwrap()
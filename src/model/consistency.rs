@@ -0,0 +1,216 @@
+//! Compares the objects in storage with the versions in the database.
+
+use std::collections::HashSet;
+
+use actix::prelude::*;
+use diesel::prelude::*;
+use elba::package::Name as PackageName;
+use failure::Error;
+use futures::Future;
+
+use crate::archive::read_archive;
+use crate::database::{Connection, Database};
+use crate::model::outbox::{EventStatus, SideEffect};
+use crate::model::packages::PackageVersion;
+use crate::schema::*;
+use crate::storage::{
+    manifest_path, readme_html_path, readme_path, tarball_object_path, tarball_path,
+    DeleteObject, FetchTarball, ListObjects, Storage, StoreObject, OBJECT_DIRS,
+};
+use crate::util::markdown;
+use crate::CONFIG;
+
+pub struct CheckStorage {
+    pub delete_orphans: bool,
+    pub restore_readmes: bool,
+}
+
+#[derive(Default)]
+pub struct StorageReport {
+    // objects that versions in the database refer to but are not stored
+    pub missing: Vec<String>,
+    // objects that no version in the database refers to
+    pub orphans: Vec<String>,
+    pub deleted: Vec<String>,
+    pub restored: Vec<String>,
+}
+
+impl Message for CheckStorage {
+    type Result = Result<StorageReport, Error>;
+}
+
+impl Handler<CheckStorage> for Database {
+    type Result = Result<StorageReport, Error>;
+
+    fn handle(&mut self, msg: CheckStorage, _: &mut Self::Context) -> Self::Result {
+        check_storage(msg, &self.connection()?, &self.storage)
+    }
+}
+
+pub fn check_storage(
+    msg: CheckStorage,
+    conn: &Connection,
+    storage: &Addr<Storage>,
+) -> Result<StorageReport, Error> {
+    let mut objects = HashSet::new();
+    for dir in OBJECT_DIRS.iter() {
+        objects.extend(
+            storage
+                .send(ListObjects {
+                    prefix: dir.to_string(),
+                }).from_err::<Error>()
+                .wait()??,
+        );
+    }
+
+    // objects of these versions are yet to be stored by the outbox worker
+    let pending: HashSet<i32> = outbox_events::table
+        .filter(outbox_events::kind.eq(SideEffect::StorePackage.as_str()))
+        .filter(outbox_events::status.ne(EventStatus::Done.as_str()))
+        .select(outbox_events::version_id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    let versions = versions::table
+        .inner_join(packages::table.inner_join(groups::table))
+        .select((
+            versions::id,
+            groups::group_name_origin,
+            packages::package_name_origin,
+            versions::semver,
+            versions::tarball_digest,
+            versions::has_readme,
+        )).load::<(i32, String, String, String, Option<String>, bool)>(conn)?;
+
+    let mut report = StorageReport::default();
+    let mut expected = HashSet::new();
+
+    for (version_id, group_name, package_name, semver, digest, has_readme) in versions {
+        let package = PackageVersion {
+            name: PackageName::new(group_name, package_name)?,
+            semver: semver.parse()?,
+        };
+
+        let tarball = tarball_object_path(&package, digest.as_ref().map(|s| s.as_str()));
+        let manifest = manifest_path(&package);
+        let readme = readme_path(&package);
+        let readme_html = readme_html_path(&package);
+
+        expected.insert(tarball.clone());
+        expected.insert(manifest.clone());
+        expected.insert(readme.clone());
+        expected.insert(readme_html.clone());
+        // copies kept at the old path in compatibility mode
+        if digest.is_some() {
+            expected.insert(tarball_path(&package));
+        }
+
+        if pending.contains(&version_id) {
+            continue;
+        }
+
+        for path in [&tarball, &manifest].iter() {
+            if !objects.contains(*path) {
+                report.missing.push(path.to_string());
+            }
+        }
+
+        // readmes are optional
+        if !has_readme {
+            continue;
+        }
+
+        let missing_readmes: Vec<&String> = [&readme, &readme_html]
+            .iter()
+            .filter(|path| !objects.contains(**path))
+            .cloned()
+            .collect();
+
+        if missing_readmes.is_empty() {
+            continue;
+        }
+
+        report
+            .missing
+            .extend(missing_readmes.iter().map(|path| path.to_string()));
+
+        if msg.restore_readmes && objects.contains(&tarball) {
+            match restore_readme(&package, digest, storage) {
+                Ok(true) => report.restored.push(readme),
+                Ok(false) => {}
+                Err(err) => warn!("Failed to restore readme `{}`: {}", &readme, err),
+            }
+        }
+    }
+
+    report.orphans = objects.difference(&expected).cloned().collect();
+    report.orphans.sort();
+    report.missing.sort();
+
+    if msg.delete_orphans {
+        for path in report.orphans.iter() {
+            let result = storage
+                .send(DeleteObject { path: path.clone() })
+                .from_err::<Error>()
+                .wait()
+                .and_then(|result| result);
+
+            match result {
+                Ok(()) => report.deleted.push(path.clone()),
+                Err(err) => warn!("Failed to delete orphan `{}`: {}", path, err),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Extracts the readme from the tarball again and stores it along with
+/// its rendered version. Returns `false` if the tarball has no readme.
+fn restore_readme(
+    package: &PackageVersion,
+    digest: Option<String>,
+    storage: &Addr<Storage>,
+) -> Result<bool, Error> {
+    let mut tarball = storage
+        .send(FetchTarball {
+            package: package.clone(),
+            digest,
+        }).from_err::<Error>()
+        .wait()??;
+
+    let max_upload_size = CONFIG
+        .publish_policy
+        .max_upload_size(package.name.normalized_group(), CONFIG.max_upload_size);
+    let archive = read_archive(&mut tarball, max_upload_size)?;
+    let (readme, readme_path) = match (archive.readme, archive.readme_path) {
+        (Some(readme), Some(readme_path)) => (readme, readme_path),
+        _ => return Ok(false),
+    };
+    let readme_html = markdown::render_readme(
+        &readme,
+        archive
+            .manifest
+            .package
+            .repository
+            .as_ref()
+            .map(|s| s.as_str()),
+        &readme_path,
+    );
+
+    storage
+        .send(StoreObject {
+            path: readme_path(package),
+            bytes: readme.into_bytes(),
+        }).from_err::<Error>()
+        .wait()??;
+    storage
+        .send(StoreObject {
+            path: readme_html_path(package),
+            bytes: readme_html.into_bytes(),
+        }).from_err::<Error>()
+        .wait()??;
+
+    Ok(true)
+}
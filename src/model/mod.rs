@@ -1,2 +1,4 @@
+pub mod consistency;
+pub mod outbox;
 pub mod packages;
 pub mod users;
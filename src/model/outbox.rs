@@ -0,0 +1,450 @@
+//! Side effects of publishing and yanking, which are recorded in the same
+//! transaction as the version row and applied afterwards by the outbox
+//! worker. This keeps the database, the storage and the git index
+//! from drifting apart when one of them fails halfway.
+
+use std::cmp;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use actix::prelude::*;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{self, prelude::*};
+use elba::package::Name as PackageName;
+use failure::{Error, ResultExt};
+use futures::Future;
+use serde_json::{self, Value};
+
+use crate::database::{Connection, Database};
+use crate::index::{Index, UpdatePackage, YankPackage};
+use crate::model::packages::*;
+use crate::outbox::OutboxProcessor;
+use crate::schema::*;
+use crate::search::{Search, UpdateSearch};
+use crate::storage::{Storage, StorePackage};
+use crate::CONFIG;
+
+// Failed events are retried with exponential backoff, up to this many times.
+const MAX_ATTEMPTS: i32 = 10;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+const BATCH_SIZE: i64 = 32;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SideEffect {
+    StorePackage,
+    UpdateSearch,
+    UpdateIndex,
+    SyncYanked,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// An event that is due, without its payload.
+#[derive(Queryable)]
+pub struct PendingEvent {
+    pub id: i32,
+    pub version_id: i32,
+    pub kind: String,
+    pub attempts: i32,
+}
+
+#[derive(Insertable)]
+#[table_name = "outbox_events"]
+pub struct CreateOutboxEvent {
+    pub version_id: i32,
+    pub kind: &'static str,
+    pub payload: Option<Value>,
+    // staged copy of the uploaded tarball, see `stage_tarball`
+    pub tarball_path: Option<String>,
+}
+
+/// Everything but the tarball that goes into storage on publish.
+#[derive(Serialize, Deserialize)]
+pub struct StorePackagePayload {
+    pub manifest: String,
+    pub readme: Option<String>,
+    pub readme_html: Option<String>,
+}
+
+/// The state of a side effect, without the error of the last attempt,
+/// which may leak details of the infrastructure and is only logged.
+pub struct SideEffectReport {
+    pub kind: SideEffect,
+    pub status: EventStatus,
+    pub attempts: i32,
+    pub created_at: NaiveDateTime,
+    pub processed_at: Option<NaiveDateTime>,
+}
+
+pub struct ProcessOutbox;
+pub struct ListSideEffects(pub PackageVersion);
+
+impl Message for ProcessOutbox {
+    type Result = Result<(), Error>;
+}
+
+impl Message for ListSideEffects {
+    type Result = Result<Vec<SideEffectReport>, Error>;
+}
+
+impl Handler<ProcessOutbox> for OutboxProcessor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: ProcessOutbox, _: &mut Self::Context) -> Self::Result {
+        process_outbox(
+            msg,
+            &self.db.connection()?,
+            &self.db.index,
+            &self.db.uploads,
+            &self.db.search,
+        )
+    }
+}
+
+impl Handler<ListSideEffects> for Database {
+    type Result = Result<Vec<SideEffectReport>, Error>;
+
+    fn handle(&mut self, msg: ListSideEffects, _: &mut Self::Context) -> Self::Result {
+        list_side_effects(msg, &self.connection()?)
+    }
+}
+
+impl CreateOutboxEvent {
+    pub fn new(version_id: i32, kind: SideEffect) -> Self {
+        CreateOutboxEvent {
+            version_id,
+            kind: kind.as_str(),
+            payload: None,
+            tarball_path: None,
+        }
+    }
+}
+
+pub fn process_outbox(
+    _: ProcessOutbox,
+    conn: &Connection,
+    index: &Addr<Index>,
+    storage: &Addr<Storage>,
+    search: &Addr<Search>,
+) -> Result<(), Error> {
+    let now = Utc::now().naive_utc();
+
+    let events = outbox_events::table
+        .filter(outbox_events::status.eq(EventStatus::Pending.as_str()))
+        .filter(outbox_events::next_attempt_at.le(now))
+        .order_by(outbox_events::id)
+        .limit(BATCH_SIZE)
+        .select((
+            outbox_events::id,
+            outbox_events::version_id,
+            outbox_events::kind,
+            outbox_events::attempts,
+        )).load::<PendingEvent>(conn)?;
+
+    // Events of a version are applied in order, so an event that is
+    // waiting for a retry or has failed holds back the other events of
+    // its version. Those are always later ones, since an event is only
+    // tried once the ones before it are done.
+    let version_ids: Vec<i32> = events.iter().map(|event| event.version_id).collect();
+    let mut blocked: HashSet<i32> = outbox_events::table
+        .filter(outbox_events::version_id.eq_any(&version_ids))
+        .filter(
+            outbox_events::status.eq(EventStatus::Failed.as_str()).or(outbox_events::status
+                .eq(EventStatus::Pending.as_str())
+                .and(outbox_events::next_attempt_at.gt(now))),
+        ).select(outbox_events::version_id)
+        .distinct()
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    for event in events {
+        if blocked.contains(&event.version_id) {
+            continue;
+        }
+
+        let attempts = event.attempts + 1;
+
+        match apply_event(&event, conn, index, storage, search) {
+            Ok(staged) => {
+                info!("Outbox: applied `{}` of event {}", &event.kind, event.id);
+
+                diesel::update(outbox_events::table.find(event.id))
+                    .set((
+                        outbox_events::status.eq(EventStatus::Done.as_str()),
+                        outbox_events::attempts.eq(attempts),
+                        outbox_events::last_error.eq(None::<String>),
+                        outbox_events::processed_at.eq(Some(Utc::now().naive_utc())),
+                        // the tarball is in storage now
+                        outbox_events::tarball_path.eq(None::<String>),
+                    )).execute(conn)?;
+
+                if let Some(path) = staged {
+                    remove_staged_tarball(&path);
+                }
+            }
+            Err(err) => {
+                blocked.insert(event.version_id);
+
+                let status = if attempts >= MAX_ATTEMPTS {
+                    error!(
+                        "Outbox: giving up `{}` of event {} after {} attempts: {}",
+                        &event.kind, event.id, attempts, err
+                    );
+                    EventStatus::Failed
+                } else {
+                    warn!(
+                        "Outbox: failed to apply `{}` of event {} (attempt {}): {}",
+                        &event.kind, event.id, attempts, err
+                    );
+                    EventStatus::Pending
+                };
+
+                let backoff = cmp::min(
+                    BASE_BACKOFF_SECS << cmp::min(attempts - 1, 16),
+                    MAX_BACKOFF_SECS,
+                );
+
+                diesel::update(outbox_events::table.find(event.id))
+                    .set((
+                        outbox_events::status.eq(status.as_str()),
+                        outbox_events::attempts.eq(attempts),
+                        outbox_events::last_error.eq(Some(err.to_string())),
+                        outbox_events::next_attempt_at
+                            .eq(Utc::now().naive_utc() + Duration::seconds(backoff)),
+                    )).execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies an uploaded tarball to `OUTBOX_STAGING_DIR`, where it is kept
+/// until the outbox worker has put it into storage.
+pub fn stage_tarball(tarball: &File) -> Result<PathBuf, Error> {
+    let dir = &CONFIG.outbox_staging_dir;
+    fs::create_dir_all(dir).with_context(|_| format!("can not create staging dir `{:?}`", dir))?;
+
+    let mut staged = tempfile::Builder::new()
+        .suffix(".tar.gz")
+        .tempfile_in(dir)?;
+    let mut tarball = tarball;
+    tarball.seek(SeekFrom::Start(0))?;
+    io::copy(&mut tarball, &mut staged)?;
+    // the event pointing at it is committed right after
+    staged.as_file().sync_all()?;
+
+    let (_, path) = staged.keep()?;
+    Ok(path)
+}
+
+pub fn remove_staged_tarball(path: &Path) {
+    if let Err(err) = fs::remove_file(path) {
+        warn!("Outbox: can not remove staged tarball `{:?}`: {}", path, err);
+    }
+}
+
+/// Applies an event. Returns the staged tarball of the event, which is
+/// removed once the event is marked as done.
+fn apply_event(
+    event: &PendingEvent,
+    conn: &Connection,
+    index: &Addr<Index>,
+    storage: &Addr<Storage>,
+    search: &Addr<Search>,
+) -> Result<Option<PathBuf>, Error> {
+    let (package, version) = lookup_version_by_id(event.version_id, conn)?;
+
+    let staged = match event.kind.parse::<SideEffect>()? {
+        SideEffect::StorePackage => {
+            let (payload, tarball_path) = outbox_events::table
+                .find(event.id)
+                .select((outbox_events::payload, outbox_events::tarball_path))
+                .first::<(Option<Value>, Option<String>)>(conn)?;
+
+            let payload =
+                payload.ok_or_else(|| format_err!("event {} has no payload", event.id))?;
+            let payload: StorePackagePayload = serde_json::from_value(payload)?;
+            let tarball_path = PathBuf::from(
+                tarball_path.ok_or_else(|| format_err!("event {} has no tarball", event.id))?,
+            );
+            let tarball = File::open(&tarball_path)
+                .with_context(|_| format!("can not open staged tarball `{:?}`", &tarball_path))?;
+
+            storage
+                .send(StorePackage {
+                    package,
+                    digest: version.tarball_digest,
+                    tarball,
+                    manifest: payload.manifest,
+                    readme: payload.readme,
+                    readme_html: payload.readme_html,
+                }).from_err::<Error>()
+                .wait()?
+                .with_context(|_| "failed to store package")?
+                .commit();
+
+            Some(tarball_path)
+        }
+        SideEffect::UpdateSearch => {
+            let keywords = list_keywords(
+                ListKeywords {
+                    version_id: version.id,
+                },
+                conn,
+            )?;
+
+            search
+                .send(UpdateSearch {
+                    name: package.name,
+                    keywords,
+                }).from_err::<Error>()
+                .wait()?
+                .with_context(|_| "failed to update search engine")?;
+
+            None
+        }
+        SideEffect::UpdateIndex => {
+            let dependencies = list_dependencies(ListDependencies(package.clone()), conn)?;
+            let checksum = version
+                .checksum
+                .ok_or_else(|| format_err!("version {} has no checksum", version.id))?;
+
+            index
+                .send(UpdatePackage {
+                    package,
+                    dependencies,
+                    checksum,
+                }).from_err::<Error>()
+                .wait()?
+                .with_context(|_| "failed to update index")?;
+
+            None
+        }
+        SideEffect::SyncYanked => {
+            // the current state is synced, so that events are idempotent
+            index
+                .send(YankPackage {
+                    package,
+                    yanked: version.yanked,
+                }).from_err::<Error>()
+                .wait()?
+                .with_context(|_| "failed to yank/unyank version")?;
+
+            None
+        }
+    };
+
+    Ok(staged)
+}
+
+pub fn list_side_effects(
+    msg: ListSideEffects,
+    conn: &Connection,
+) -> Result<Vec<SideEffectReport>, Error> {
+    let (_, version) = lookup_version(LookupVersion(msg.0), conn)?;
+
+    let events = outbox_events::table
+        .filter(outbox_events::version_id.eq(version.id))
+        .order_by(outbox_events::id)
+        .select((
+            outbox_events::kind,
+            outbox_events::status,
+            outbox_events::attempts,
+            outbox_events::created_at,
+            outbox_events::processed_at,
+        )).load::<(String, String, i32, NaiveDateTime, Option<NaiveDateTime>)>(conn)?;
+
+    let reports = events
+        .into_iter()
+        .filter_map(|(kind, status, attempts, created_at, processed_at)| {
+            Some(SideEffectReport {
+                kind: kind.parse().ok()?,
+                status: status.parse().ok()?,
+                attempts,
+                created_at,
+                processed_at,
+            })
+        }).collect();
+
+    Ok(reports)
+}
+
+fn lookup_version_by_id(id: i32, conn: &Connection) -> Result<(PackageVersion, Version), Error> {
+    let (group_name, package_name, version) = versions::table
+        .inner_join(packages::table.inner_join(groups::table))
+        .filter(versions::id.eq(id))
+        .select((
+            groups::group_name_origin,
+            packages::package_name_origin,
+            versions::all_columns,
+        )).first::<(String, String, Version)>(conn)?;
+
+    Ok((
+        PackageVersion {
+            name: PackageName::new(group_name, package_name)?,
+            semver: version.semver.parse()?,
+        },
+        version,
+    ))
+}
+
+impl SideEffect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SideEffect::StorePackage => "store_package",
+            SideEffect::UpdateSearch => "update_search",
+            SideEffect::UpdateIndex => "update_index",
+            SideEffect::SyncYanked => "sync_yanked",
+        }
+    }
+}
+
+impl FromStr for SideEffect {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "store_package" => Ok(SideEffect::StorePackage),
+            "update_search" => Ok(SideEffect::UpdateSearch),
+            "update_index" => Ok(SideEffect::UpdateIndex),
+            "sync_yanked" => Ok(SideEffect::SyncYanked),
+            _ => bail!("unknown side effect `{}`", kind),
+        }
+    }
+}
+
+impl EventStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventStatus::Pending => "pending",
+            EventStatus::Done => "done",
+            EventStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for EventStatus {
+    type Err = Error;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(EventStatus::Pending),
+            "done" => Ok(EventStatus::Done),
+            "failed" => Ok(EventStatus::Failed),
+            _ => bail!("unknown event status `{}`", status),
+        }
+    }
+}
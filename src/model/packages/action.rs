@@ -1,34 +1,46 @@
+use std::fs::File;
+
 use actix::prelude::*;
-use bytes::Bytes;
 use chrono::offset::Utc;
 use diesel::{self, pg::upsert::on_constraint, prelude::*};
 use elba::package::{manifest::PackageInfo, Name as PackageName};
 use failure::{Error, ResultExt};
 use futures::Future;
+use itertools::Itertools;
+use serde_json;
 
 use crate::database::{Connection, Database};
-use crate::index::{Index, UpdatePackage, YankPackage};
-use crate::model::users::{lookup_user_by_token, LookupUserByToken, User};
+use crate::model::outbox::{
+    remove_staged_tarball, stage_tarball, CreateOutboxEvent, SideEffect, StorePackagePayload,
+};
+use crate::model::users::User;
 use crate::schema::*;
 use crate::search::{Search, UpdateSearch};
-use crate::storage::{Storage, StorePackage};
 use crate::util::error::Reason;
+use crate::util::license::License;
+use crate::CONFIG;
 
 use super::schema::*;
 use super::*;
 
 pub struct PublishVersion {
     pub package_info: PackageInfo,
+    pub license: Option<License>,
+    pub manifest_file: String,
     pub readme_file: Option<String>,
+    pub readme_html: Option<String>,
+    pub files: Vec<PackageFile>,
     pub dependencies: Vec<(DependencyReq)>,
-    pub token: String,
-    pub bytes: Bytes,
+    pub user_id: i32,
+    pub tarball: File,
+    pub checksum: String,
+    pub dry_run: bool,
 }
 
 pub struct YankVersion {
     pub package: PackageVersion,
     pub yanked: bool,
-    pub token: String,
+    pub user_id: i32,
 }
 
 pub struct ListGroups;
@@ -41,6 +53,28 @@ pub struct ListKeywords {
     pub version_id: i32,
 }
 
+pub struct ListAuthors {
+    pub version_id: i32,
+}
+
+/// Authors are stored as written in manifests, e.g. `Jane Doe <jane@x.org>`.
+/// They are matched by the name before the email, ignoring case, so both
+/// `Jane Doe` and `jane doe <jane@x.org>` find the versions above.
+pub struct ListVersionsByAuthor {
+    pub name: String,
+}
+
+pub struct ListPackagesByLicense {
+    pub license: String,
+}
+
+pub struct ListFiles(pub PackageVersion);
+
+pub struct LookupFile {
+    pub package: PackageVersion,
+    pub path: String,
+}
+
 pub struct LookupGroup(pub GroupName);
 pub struct LookupPackage(pub PackageName);
 pub struct LookupVersion(pub PackageVersion);
@@ -53,7 +87,7 @@ pub struct PopulateSearch;
 pub struct ShowGlobalStats;
 
 impl Message for PublishVersion {
-    type Result = Result<(), Error>;
+    type Result = Result<PublishReport, Error>;
 }
 
 impl Message for YankVersion {
@@ -84,6 +118,26 @@ impl Message for ListKeywords {
     type Result = Result<Vec<String>, Error>;
 }
 
+impl Message for ListAuthors {
+    type Result = Result<Vec<String>, Error>;
+}
+
+impl Message for ListVersionsByAuthor {
+    type Result = Result<Vec<PackageVersion>, Error>;
+}
+
+impl Message for ListPackagesByLicense {
+    type Result = Result<Vec<PackageName>, Error>;
+}
+
+impl Message for ListFiles {
+    type Result = Result<Vec<PackageFile>, Error>;
+}
+
+impl Message for LookupFile {
+    type Result = Result<PackageFile, Error>;
+}
+
 impl Message for LookupGroup {
     type Result = Result<(GroupName, Group), Error>;
 }
@@ -117,16 +171,10 @@ impl Message for ShowGlobalStats {
 }
 
 impl Handler<PublishVersion> for Database {
-    type Result = Result<(), Error>;
+    type Result = Result<PublishReport, Error>;
 
     fn handle(&mut self, msg: PublishVersion, _: &mut Self::Context) -> Self::Result {
-        publish_version(
-            msg,
-            &self.connection()?,
-            &self.index,
-            &self.storage,
-            &self.search,
-        )
+        publish_version(msg, &self.connection()?)
     }
 }
 
@@ -134,7 +182,7 @@ impl Handler<YankVersion> for Database {
     type Result = Result<(), Error>;
 
     fn handle(&mut self, msg: YankVersion, _: &mut Self::Context) -> Self::Result {
-        yank_version(msg, &self.connection()?, &self.index)
+        yank_version(msg, &self.connection()?)
     }
 }
 
@@ -186,6 +234,46 @@ impl Handler<ListKeywords> for Database {
     }
 }
 
+impl Handler<ListAuthors> for Database {
+    type Result = Result<Vec<String>, Error>;
+
+    fn handle(&mut self, msg: ListAuthors, _: &mut Self::Context) -> Self::Result {
+        list_authors(msg, &self.connection()?)
+    }
+}
+
+impl Handler<ListVersionsByAuthor> for Database {
+    type Result = Result<Vec<PackageVersion>, Error>;
+
+    fn handle(&mut self, msg: ListVersionsByAuthor, _: &mut Self::Context) -> Self::Result {
+        list_versions_by_author(msg, &self.connection()?)
+    }
+}
+
+impl Handler<ListPackagesByLicense> for Database {
+    type Result = Result<Vec<PackageName>, Error>;
+
+    fn handle(&mut self, msg: ListPackagesByLicense, _: &mut Self::Context) -> Self::Result {
+        list_packages_by_license(msg, &self.connection()?)
+    }
+}
+
+impl Handler<ListFiles> for Database {
+    type Result = Result<Vec<PackageFile>, Error>;
+
+    fn handle(&mut self, msg: ListFiles, _: &mut Self::Context) -> Self::Result {
+        list_files(msg, &self.connection()?)
+    }
+}
+
+impl Handler<LookupFile> for Database {
+    type Result = Result<PackageFile, Error>;
+
+    fn handle(&mut self, msg: LookupFile, _: &mut Self::Context) -> Self::Result {
+        lookup_file(msg, &self.connection()?)
+    }
+}
+
 impl Handler<LookupGroup> for Database {
     type Result = Result<(GroupName, Group), Error>;
 
@@ -250,33 +338,31 @@ impl Handler<ShowGlobalStats> for Database {
     }
 }
 
-pub fn publish_version(
-    msg: PublishVersion,
-    conn: &Connection,
-    index: &Addr<Index>,
-    storage: &Addr<Storage>,
-    search: &Addr<Search>,
-) -> Result<(), Error> {
-    conn.build_transaction().serializable().run(|| {
-        let user = lookup_user_by_token(
-            LookupUserByToken {
-                access_token: msg.token.clone(),
-            },
-            conn,
-        )?;
+pub fn publish_version(msg: PublishVersion, conn: &Connection) -> Result<PublishReport, Error> {
+    // Dry runs go through the whole transaction and then bail out
+    // with this error, so every change made so far gets rolled back.
+    #[derive(Debug, Fail)]
+    #[fail(display = "rollback of dry run")]
+    struct DryRunRollback;
+
+    let mut report = None;
+    let mut staged = None;
 
+    let result = conn.build_transaction().serializable().run(|| {
         let group = groups::table
             .filter(groups::columns::group_name.eq(&msg.package_info.name.normalized_group()))
             .first::<Group>(conn)
             .optional()?;
 
+        let new_group = group.is_none();
+
         let group = match group {
             Some(group) => group,
             None => diesel::insert_into(groups::table)
                 .values(CreateGroup {
                     group_name: &msg.package_info.name.normalized_group(),
                     group_name_origin: &msg.package_info.name.group(),
-                    user_id: user.id,
+                    user_id: msg.user_id,
                 }).get_result(conn)?,
         };
 
@@ -285,12 +371,14 @@ pub fn publish_version(
             .first::<Package>(conn)
             .optional()?;
 
+        let new_package = package.is_none();
+
         let mut package = match package {
             Some(package) => {
                 let package_owners =
                     PackageOwner::belonging_to(&package).load::<PackageOwner>(conn)?;
 
-                if !package_owners.iter().any(|owner| owner.user_id == user.id) {
+                if !package_owners.iter().any(|owner| owner.user_id == msg.user_id) {
                     return Err(human!(
                         Reason::NoPermission,
                         "You have no access permission to package `{}`",
@@ -301,7 +389,7 @@ pub fn publish_version(
                 package
             }
             None => {
-                if group.user_id != user.id {
+                if group.user_id != msg.user_id {
                     return Err(human!(
                         Reason::NoPermission,
                         "You have no permission to create package under group `{}`",
@@ -319,31 +407,50 @@ pub fn publish_version(
                 diesel::insert_into(package_owners::table)
                     .values(CreateOwner {
                         package_id: package.id,
-                        user_id: user.id,
+                        user_id: msg.user_id,
                     }).execute(conn)?;
 
                 package
             }
         };
 
-        package.updated_at = Utc::now().naive_utc();
-        let connection: &PgConnection = &*conn;
-        let package: Package = package.save_changes(connection)?;
-
         let version = Version::belonging_to(&package)
             .filter(versions::columns::semver.eq(msg.package_info.version.to_string()))
             .first::<Version>(conn)
             .optional()?;
 
-        if version.is_some() {
-            return Err(human!(
-                Reason::NoPermission,
-                "Package `{} {}` already exists",
-                &msg.package_info.name.as_str(),
-                &msg.package_info.version,
-            ));
+        if let Some(version) = version {
+            if version.checksum.as_ref() != Some(&msg.checksum) {
+                return Err(human!(
+                    Reason::NoPermission,
+                    "Package `{} {}` already exists",
+                    &msg.package_info.name.as_str(),
+                    &msg.package_info.version,
+                ));
+            }
+
+            // The very same tarball was published before, most likely by a
+            // retry after a timeout. Nothing needs to change.
+            let owners = PackageOwner::belonging_to(&package)
+                .inner_join(users::table)
+                .select(users::all_columns)
+                .load::<User>(conn)?;
+
+            report = Some(PublishReport {
+                new_group,
+                new_package,
+                owners,
+                dependencies: msg.dependencies.clone(),
+                no_op: true,
+            });
+
+            return Ok(());
         }
 
+        package.updated_at = Utc::now().naive_utc();
+        let connection: &PgConnection = &*conn;
+        let package: Package = package.save_changes(connection)?;
+
         let version = diesel::insert_into(versions::table)
             .values(CreateVersion {
                 package_id: package.id,
@@ -351,11 +458,33 @@ pub fn publish_version(
                 description: msg.package_info.description.as_ref().map(|s| s.as_str()),
                 homepage: msg.package_info.homepage.as_ref().map(|s| s.as_str()),
                 repository: msg.package_info.repository.as_ref().map(|s| s.as_str()),
-                license: msg.package_info.license.as_ref().map(|s| s.as_str()),
+                license: msg.license.as_ref().map(|l| l.expression.as_str()),
+                checksum: &msg.checksum,
+                tarball_digest: if CONFIG.content_addressed_tarballs {
+                    Some(msg.checksum.as_str())
+                } else {
+                    None
+                },
+                has_readme: msg.readme_file.is_some(),
             }).get_result::<Version>(conn)?;
 
-        let mut deps_info = Vec::new();
+        let mut create_deps = Vec::new();
+        let mut unsatisfied = Vec::new();
         for dep_req in msg.dependencies.iter() {
+            // packages in foreign indices are not known to this registry
+            if let Some(index) = &dep_req.index {
+                create_deps.push(CreateDependency {
+                    version_id: version.id,
+                    package_id: None,
+                    version_req: dep_req.version_req.to_string(),
+                    kind: dep_req.kind.as_str(),
+                    foreign_index: Some(index.to_string()),
+                    foreign_group: Some(dep_req.name.group().to_owned()),
+                    foreign_name: Some(dep_req.name.name().to_owned()),
+                });
+                continue;
+            }
+
             let dep_id = packages::table
                 .inner_join(groups::table)
                 .filter(groups::group_name.eq(&dep_req.name.normalized_group()))
@@ -364,24 +493,62 @@ pub fn publish_version(
                 .get_result::<i32>(conn)
                 .optional()?;
 
-            if let Some(dep_id) = dep_id {
-                deps_info.push((dep_id, dep_req.version_req.clone()));
-            } else {
-                return Err(human!(
-                    Reason::DependencyNotFound,
-                    "Dependency `{}` not found in index",
-                    dep_req.name.as_str()
+            let dep_id = match dep_id {
+                Some(dep_id) => dep_id,
+                None => {
+                    return Err(human!(
+                        Reason::DependencyNotFound,
+                        "Dependency `{}` not found in index",
+                        dep_req.name.as_str()
+                    ))
+                }
+            };
+
+            let mut dep_versions: Vec<semver::Version> = versions::table
+                .filter(versions::package_id.eq(dep_id))
+                .filter(versions::yanked.eq(false))
+                .select(versions::semver)
+                .load::<String>(conn)?
+                .into_iter()
+                .filter_map(|dep_version| dep_version.parse().ok())
+                .collect();
+            dep_versions.sort();
+
+            if !dep_versions
+                .iter()
+                .any(|dep_version| dep_req.version_req.satisfies(dep_version))
+            {
+                let available = if dep_versions.is_empty() {
+                    "none".to_owned()
+                } else {
+                    dep_versions.iter().join(", ")
+                };
+                unsatisfied.push(format!(
+                    "`{} {}` (available versions: {})",
+                    dep_req.name.as_str(),
+                    &dep_req.version_req,
+                    available
                 ));
             }
-        }
 
-        let create_deps: Vec<CreateDependency> = deps_info
-            .iter()
-            .map(|dep_info| CreateDependency {
+            create_deps.push(CreateDependency {
                 version_id: version.id,
-                package_id: dep_info.0,
-                version_req: dep_info.1.to_string(),
-            }).collect();
+                package_id: Some(dep_id),
+                version_req: dep_req.version_req.to_string(),
+                kind: dep_req.kind.as_str(),
+                foreign_index: None,
+                foreign_group: None,
+                foreign_name: None,
+            });
+        }
+
+        if !unsatisfied.is_empty() {
+            return Err(human!(
+                Reason::DependencyNotFound,
+                "No published version satisfies dependency {}",
+                unsatisfied.join(", ")
+            ));
+        }
 
         diesel::insert_into(dependencies::table)
             .values(create_deps)
@@ -400,6 +567,37 @@ pub fn publish_version(
             .values(create_authors)
             .execute(conn)?;
 
+        if let Some(license) = &msg.license {
+            let create_licenses: Vec<CreateLicense> = license
+                .ids
+                .iter()
+                .map(|license| CreateLicense {
+                    version_id: version.id,
+                    license,
+                }).collect();
+
+            diesel::insert_into(version_licenses::table)
+                .values(create_licenses)
+                .execute(conn)?;
+        }
+
+        let create_files: Vec<CreateVersionFile> = msg
+            .files
+            .iter()
+            .map(|file| CreateVersionFile {
+                version_id: version.id,
+                path: &file.path,
+                size: file.size as i64,
+                mode: file.mode as i32,
+            }).collect();
+
+        // keep clear of the bind parameter limit of postgres
+        for create_files in create_files.chunks(1024) {
+            diesel::insert_into(version_files::table)
+                .values(create_files)
+                .execute(conn)?;
+        }
+
         let create_keywords: Vec<CreateKeyword> = msg
             .package_info
             .keywords
@@ -413,58 +611,76 @@ pub fn publish_version(
             .values(create_keywords)
             .execute(conn)?;
 
-        let storage_transaction = storage
-            .send(StorePackage {
-                package: PackageVersion {
-                    name: msg.package_info.name.clone(),
-                    semver: msg.package_info.version.clone(),
-                },
-                bytes: msg.bytes,
-                readme: msg.readme_file,
-            }).from_err::<Error>()
-            .wait()?
-            .with_context(|_| "failed to store package")?;
+        let owners = PackageOwner::belonging_to(&package)
+            .inner_join(users::table)
+            .select(users::all_columns)
+            .load::<User>(conn)?;
+
+        report = Some(PublishReport {
+            new_group,
+            new_package,
+            owners,
+            dependencies: msg.dependencies.clone(),
+            no_op: false,
+        });
+
+        if msg.dry_run {
+            return Err(DryRunRollback.into());
+        }
 
-        search
-            .send(UpdateSearch {
-                name: msg.package_info.name.clone(),
-                keywords: msg.package_info.keywords.clone(),
-            }).from_err::<Error>()
-            .wait()?
-            .with_context(|_| "failed to update search engine")?;
+        // Storage, search and index are updated by the outbox worker once
+        // the version is committed, in this order.
+        let tarball_path = stage_tarball(&msg.tarball)?;
+        staged = Some(tarball_path.clone());
+        let tarball_path = tarball_path
+            .to_str()
+            .ok_or_else(|| format_err!("invalid staging path `{:?}`", &tarball_path))?
+            .to_owned();
+
+        let payload = StorePackagePayload {
+            manifest: msg.manifest_file,
+            readme: msg.readme_file,
+            readme_html: msg.readme_html,
+        };
 
-        index
-            .send(UpdatePackage {
-                package: PackageVersion {
-                    name: msg.package_info.name,
-                    semver: msg.package_info.version,
-                },
-                dependencies: msg.dependencies,
-            }).from_err::<Error>()
-            .wait()?
-            .with_context(|_| "failed to update index")?;
+        let create_events = vec![
+            CreateOutboxEvent {
+                payload: Some(serde_json::to_value(payload)?),
+                tarball_path: Some(tarball_path),
+                ..CreateOutboxEvent::new(version.id, SideEffect::StorePackage)
+            },
+            CreateOutboxEvent::new(version.id, SideEffect::UpdateSearch),
+            CreateOutboxEvent::new(version.id, SideEffect::UpdateIndex),
+        ];
 
-        storage_transaction.commit();
+        diesel::insert_into(outbox_events::table)
+            .values(create_events)
+            .execute(conn)?;
 
         Ok(())
-    })
+    });
+
+    if let Err(err) = result {
+        // no event points at the staged tarball
+        if let Some(path) = staged {
+            remove_staged_tarball(&path);
+        }
+        if err.downcast_ref::<DryRunRollback>().is_none() {
+            return Err(err);
+        }
+    }
+
+    Ok(report.expect("publish report is missing"))
 }
 
-pub fn yank_version(msg: YankVersion, conn: &Connection, index: &Addr<Index>) -> Result<(), Error> {
+pub fn yank_version(msg: YankVersion, conn: &Connection) -> Result<(), Error> {
     conn.build_transaction().serializable().run(|| {
-        let user = lookup_user_by_token(
-            LookupUserByToken {
-                access_token: msg.token.clone(),
-            },
-            conn,
-        )?;
-
         let (_, package) = lookup_package(LookupPackage(msg.package.name.clone()), conn)?;
         let (_, version) = lookup_version(LookupVersion(msg.package.clone()), conn)?;
 
         let package_owners = PackageOwner::belonging_to(&package).load::<PackageOwner>(conn)?;
 
-        if !package_owners.iter().any(|owner| owner.user_id == user.id) {
+        if !package_owners.iter().any(|owner| owner.user_id == msg.user_id) {
             return Err(human!(
                 Reason::NoPermission,
                 "You have no access permission to package `{}`",
@@ -491,13 +707,9 @@ pub fn yank_version(msg: YankVersion, conn: &Connection, index: &Addr<Index>) ->
             .set(versions::yanked.eq(msg.yanked))
             .execute(conn)?;
 
-        index
-            .send(YankPackage {
-                package: msg.package,
-                yanked: msg.yanked,
-            }).from_err::<Error>()
-            .wait()?
-            .with_context(|_| "failed to yank/unyank version")?;
+        diesel::insert_into(outbox_events::table)
+            .values(CreateOutboxEvent::new(version.id, SideEffect::SyncYanked))
+            .execute(conn)?;
 
         Ok(())
     })
@@ -589,15 +801,30 @@ pub fn list_dependencies(
             dependencies::all_columns(),
         )).load::<((String, String, Dependency))>(conn)?;
 
-    let package_dependencies: Vec<_> = result
+    let mut package_dependencies: Vec<_> = result
         .into_iter()
         .filter_map(|(groups_name, packages_name, dependency)| {
             Some(DependencyReq {
                 name: PackageName::new(groups_name, packages_name).ok()?,
                 version_req: dependency.version_req.parse().ok()?,
+                kind: dependency.kind.parse().ok()?,
+                index: None,
             })
         }).collect();
 
+    let foreign_dependencies = Dependency::belonging_to(&version)
+        .filter(package_id.is_null())
+        .load::<Dependency>(conn)?;
+
+    package_dependencies.extend(foreign_dependencies.into_iter().filter_map(|dependency| {
+        Some(DependencyReq {
+            name: PackageName::new(dependency.foreign_group?, dependency.foreign_name?).ok()?,
+            version_req: dependency.version_req.parse().ok()?,
+            kind: dependency.kind.parse().ok()?,
+            index: Some(dependency.foreign_index?.parse().ok()?),
+        })
+    }));
+
     Ok(package_dependencies)
 }
 
@@ -612,6 +839,115 @@ pub fn list_keywords(msg: ListKeywords, conn: &Connection) -> Result<Vec<String>
     Ok(keywords)
 }
 
+pub fn list_authors(msg: ListAuthors, conn: &Connection) -> Result<Vec<String>, Error> {
+    use crate::schema::version_authors::dsl::*;
+
+    let authors = version_authors
+        .select(name)
+        .filter(version_id.eq(msg.version_id))
+        .order_by(id)
+        .load::<String>(conn)?;
+
+    Ok(authors)
+}
+
+pub fn list_versions_by_author(
+    msg: ListVersionsByAuthor,
+    conn: &Connection,
+) -> Result<Vec<PackageVersion>, Error> {
+    use crate::schema::groups::dsl::*;
+    use crate::schema::packages::dsl::*;
+    use crate::schema::version_authors::dsl::*;
+    use crate::schema::versions::dsl::*;
+
+    let author = escape_like(msg.name.split('<').next().unwrap_or("").trim());
+
+    let result = version_authors
+        .inner_join(versions.inner_join(packages.inner_join(groups)))
+        .filter(name.ilike(&author).or(name.ilike(format!("{} <%", &author))))
+        .select((group_name_origin, package_name_origin, semver))
+        .distinct()
+        .load::<(String, String, String)>(conn)?;
+
+    let package_versions: Vec<_> = result
+        .into_iter()
+        .filter_map(|(groups_name, packages_name, version)| {
+            Some(PackageVersion {
+                name: PackageName::new(groups_name, packages_name).ok()?,
+                semver: version.parse().ok()?,
+            })
+        }).collect();
+
+    Ok(package_versions)
+}
+
+/// Escapes the wildcards of a `LIKE` pattern.
+fn escape_like(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if c == '\\' || c == '%' || c == '_' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn list_packages_by_license(
+    msg: ListPackagesByLicense,
+    conn: &Connection,
+) -> Result<Vec<PackageName>, Error> {
+    // `versions` has a `license` column too, so the tables are not glob imported
+    let result = version_licenses::table
+        .inner_join(versions::table.inner_join(packages::table.inner_join(groups::table)))
+        .filter(version_licenses::license.eq(&msg.license))
+        .select((groups::group_name_origin, packages::package_name_origin))
+        .distinct()
+        .load::<(String, String)>(conn)?;
+
+    let package_names: Vec<_> = result
+        .into_iter()
+        .filter_map(|(groups_name, packages_name)| {
+            PackageName::new(groups_name, packages_name).ok()
+        }).collect();
+
+    Ok(package_names)
+}
+
+pub fn list_files(msg: ListFiles, conn: &Connection) -> Result<Vec<PackageFile>, Error> {
+    use crate::schema::version_files::dsl::*;
+
+    let (_, version) = lookup_version(LookupVersion(msg.0), conn)?;
+
+    let files = VersionFile::belonging_to(&version)
+        .order_by(path)
+        .load::<VersionFile>(conn)?;
+
+    Ok(files.into_iter().map(PackageFile::from).collect())
+}
+
+pub fn lookup_file(msg: LookupFile, conn: &Connection) -> Result<PackageFile, Error> {
+    use crate::schema::version_files::dsl::*;
+
+    let (package_version, version) = lookup_version(LookupVersion(msg.package), conn)?;
+
+    let file = VersionFile::belonging_to(&version)
+        .filter(path.eq(&msg.path))
+        .first::<VersionFile>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            human!(
+                Reason::PackageNotFound,
+                "File `{}` not found in package `{} {}`",
+                &msg.path,
+                package_version.name.as_str(),
+                &package_version.semver
+            )
+        })?;
+
+    Ok(file.into())
+}
+
 pub fn lookup_group(msg: LookupGroup, conn: &Connection) -> Result<(GroupName, Group), Error> {
     use crate::schema::groups::dsl::*;
 
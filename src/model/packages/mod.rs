@@ -1,12 +1,17 @@
 mod action;
 mod schema;
 
+use std::str::FromStr;
+
 use chrono::NaiveDate;
 use elba::package::Name as PackageName;
+use elba::remote::resolution::IndexRes;
 use failure::Error;
 use semver;
 use semver_constraints::Constraint;
 
+use crate::model::users::User;
+
 pub use self::action::*;
 pub use self::schema::*;
 
@@ -25,6 +30,33 @@ pub struct PackageVersion {
 pub struct DependencyReq {
     pub name: PackageName,
     pub version_req: Constraint,
+    pub kind: DependencyKind,
+    // `None` for packages in this registry
+    pub index: Option<IndexRes>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    Normal,
+    Dev,
+}
+
+/// What a publish creates in the registry.
+pub struct PublishReport {
+    pub new_group: bool,
+    pub new_package: bool,
+    pub owners: Vec<User>,
+    pub dependencies: Vec<DependencyReq>,
+    /// Whether the version was already published with the same tarball.
+    pub no_op: bool,
+}
+
+#[derive(Clone)]
+pub struct PackageFile {
+    pub path: String,
+    pub size: u64,
+    pub mode: u32,
 }
 
 #[derive(Clone)]
@@ -76,6 +108,37 @@ impl GroupName {
     }
 }
 
+impl DependencyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Dev => "dev",
+        }
+    }
+}
+
+impl FromStr for DependencyKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "normal" => Ok(DependencyKind::Normal),
+            "dev" => Ok(DependencyKind::Dev),
+            _ => bail!("unknown dependency kind `{}`", kind),
+        }
+    }
+}
+
+impl From<VersionFile> for PackageFile {
+    fn from(file: VersionFile) -> PackageFile {
+        PackageFile {
+            path: file.path,
+            size: file.size as u64,
+            mode: file.mode as u32,
+        }
+    }
+}
+
 pub fn group_of_package(name: &PackageName) -> GroupName {
     GroupName {
         group: name.group().into(),
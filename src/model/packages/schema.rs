@@ -34,6 +34,11 @@ pub struct Version {
     pub repository: Option<String>,
     pub license: Option<String>,
     pub created_at: NaiveDateTime,
+    pub checksum: Option<String>,
+    // `None` if the tarball is stored under `storage::tarball_path`
+    pub tarball_digest: Option<String>,
+    // whether a readme was stored on publish
+    pub has_readme: bool,
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -42,8 +47,22 @@ pub struct Version {
 pub struct Dependency {
     pub id: i32,
     pub version_id: i32,
-    pub package_id: i32,
+    pub package_id: Option<i32>,
     pub version_req: String,
+    pub kind: String,
+    pub foreign_index: Option<String>,
+    pub foreign_group: Option<String>,
+    pub foreign_name: Option<String>,
+}
+
+#[derive(Identifiable, Queryable, Associations)]
+#[belongs_to(Version)]
+pub struct VersionFile {
+    pub id: i32,
+    pub version_id: i32,
+    pub path: String,
+    pub size: i64,
+    pub mode: i32,
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -79,14 +98,21 @@ pub struct CreateVersion<'a> {
     pub homepage: Option<&'a str>,
     pub repository: Option<&'a str>,
     pub license: Option<&'a str>,
+    pub checksum: &'a str,
+    pub tarball_digest: Option<&'a str>,
+    pub has_readme: bool,
 }
 
 #[derive(Insertable)]
 #[table_name = "dependencies"]
 pub struct CreateDependency {
     pub version_id: i32,
-    pub package_id: i32,
+    pub package_id: Option<i32>,
     pub version_req: String,
+    pub kind: &'static str,
+    pub foreign_index: Option<String>,
+    pub foreign_group: Option<String>,
+    pub foreign_name: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -103,6 +129,22 @@ pub struct CreateKeyword<'a> {
     pub keyword: &'a str,
 }
 
+#[derive(Insertable)]
+#[table_name = "version_licenses"]
+pub struct CreateLicense<'a> {
+    pub version_id: i32,
+    pub license: &'a str,
+}
+
+#[derive(Insertable)]
+#[table_name = "version_files"]
+pub struct CreateVersionFile<'a> {
+    pub version_id: i32,
+    pub path: &'a str,
+    pub size: i64,
+    pub mode: i32,
+}
+
 #[derive(Insertable)]
 #[table_name = "package_owners"]
 pub struct CreateOwner {
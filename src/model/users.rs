@@ -129,6 +129,14 @@ impl Handler<LookupUser> for Database {
     }
 }
 
+impl Handler<LookupUserByToken> for Database {
+    type Result = Result<User, Error>;
+
+    fn handle(&mut self, msg: LookupUserByToken, _: &mut Self::Context) -> Self::Result {
+        lookup_user_by_token(msg, &self.connection()?)
+    }
+}
+
 pub fn create_user_or_login(msg: CreateUserOrLogin, conn: &Connection) -> Result<User, Error> {
     use crate::schema::users::dsl::*;
 
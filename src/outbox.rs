@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use actix::prelude::*;
+
+use crate::database::Database;
+use crate::model::outbox::ProcessOutbox;
+
+const PROCESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically applies pending side effects recorded in the outbox.
+pub struct OutboxWorker {
+    processor: Addr<OutboxProcessor>,
+    processing: bool,
+}
+
+/// Applies the events on a thread of its own, since it blocks while
+/// waiting for storage, index and search, and would otherwise hold up
+/// a database thread serving requests.
+pub struct OutboxProcessor {
+    pub db: Database,
+}
+
+impl OutboxWorker {
+    pub fn new(processor: Addr<OutboxProcessor>) -> Self {
+        OutboxWorker {
+            processor,
+            processing: false,
+        }
+    }
+
+    fn process(&mut self, ctx: &mut Context<Self>) {
+        // a slow round (e.g. a large upload) must not overlap with the next
+        if self.processing {
+            return;
+        }
+        self.processing = true;
+
+        let future = self
+            .processor
+            .send(ProcessOutbox)
+            .into_actor(self)
+            .map(|res, worker, _| {
+                worker.processing = false;
+                if let Err(err) = res {
+                    error!("Outbox: failed to process events: {}", err);
+                }
+            }).map_err(|err, worker, _| {
+                worker.processing = false;
+                error!("Outbox: processor is unavailable: {}", err);
+            });
+
+        ctx.spawn(future);
+    }
+}
+
+impl Actor for OutboxWorker {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(PROCESS_INTERVAL, |worker, ctx| worker.process(ctx));
+    }
+}
+
+impl Actor for OutboxProcessor {
+    type Context = SyncContext<Self>;
+}
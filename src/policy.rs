@@ -0,0 +1,330 @@
+//! Rules a package has to follow to be published in this registry.
+//!
+//! Policies are loaded from the configuration and checked against what
+//! is known about an upload, independently of how the upload arrived.
+
+use std::collections::HashMap;
+
+use elba::package::manifest::PackageInfo;
+use failure::Error;
+
+use crate::util::error::Reason;
+use crate::util::license::License;
+
+#[derive(Debug, Clone)]
+pub struct PublishPolicy {
+    pub max_description_len: usize,
+    pub max_keywords: usize,
+    pub require_license: bool,
+    pub require_repository: bool,
+    pub require_readme: bool,
+    // canonical SPDX identifiers and license references, `None` if every
+    // license is allowed
+    pub allowed_licenses: Option<Vec<String>>,
+    // normalized group name to max tarball size in bytes
+    pub group_max_upload_size: HashMap<String, usize>,
+    // normalized group names
+    pub no_prerelease_groups: Vec<String>,
+}
+
+/// What the policy is checked against, gathered from an upload.
+pub struct PublishCandidate<'a> {
+    pub package: &'a PackageInfo,
+    pub license: Option<&'a License>,
+    pub has_readme: bool,
+    pub tarball_size: usize,
+}
+
+impl Default for PublishPolicy {
+    fn default() -> Self {
+        PublishPolicy {
+            max_description_len: 244,
+            max_keywords: 5,
+            require_license: false,
+            require_repository: false,
+            require_readme: false,
+            allowed_licenses: None,
+            group_max_upload_size: HashMap::new(),
+            no_prerelease_groups: Vec::new(),
+        }
+    }
+}
+
+impl PublishPolicy {
+    /// The max tarball size of a group, falling back to `default`.
+    pub fn max_upload_size(&self, group: &str, default: usize) -> usize {
+        self.group_max_upload_size
+            .get(group)
+            .cloned()
+            .unwrap_or(default)
+    }
+
+    /// Checks the candidate against every rule and reports all violations
+    /// at once, so publishers do not have to fix them one by one.
+    pub fn check(&self, candidate: &PublishCandidate) -> Result<(), Error> {
+        let violations = self.violations(candidate);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(human!(
+                Reason::PolicyViolation,
+                "Package violates the policy of this registry: {}",
+                violations.join("; ")
+            ))
+        }
+    }
+
+    pub fn violations(&self, candidate: &PublishCandidate) -> Vec<String> {
+        let package = candidate.package;
+        let group = package.name.normalized_group();
+        let mut violations = Vec::new();
+
+        if let Some(description) = &package.description {
+            if description.len() > self.max_description_len {
+                violations.push(format!(
+                    "description is over {} characters",
+                    self.max_description_len
+                ));
+            }
+        }
+
+        if package.keywords.len() > self.max_keywords {
+            violations.push(format!("no more than {} keywords", self.max_keywords));
+        }
+
+        if self.require_license && candidate.license.is_none() {
+            violations.push("a license is required".to_owned());
+        }
+
+        if let (Some(allowed), Some(license)) = (&self.allowed_licenses, candidate.license) {
+            // references are only allowed if they are named explicitly
+            let disallowed: Vec<&str> = license
+                .ids
+                .iter()
+                .chain(license.refs.iter())
+                .filter(|id| !allowed.contains(id))
+                .map(|id| id.as_str())
+                .collect();
+
+            if !disallowed.is_empty() {
+                violations.push(format!(
+                    "license {} is not allowed (allowed licenses: {})",
+                    disallowed.join(", "),
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        if self.require_repository && package.repository.is_none() {
+            violations.push("a repository url is required".to_owned());
+        }
+
+        if self.require_readme && !candidate.has_readme {
+            violations.push("a readme is required".to_owned());
+        }
+
+        if package.version.is_prerelease()
+            && self.no_prerelease_groups.iter().any(|name| name == group)
+        {
+            violations.push(format!(
+                "pre-release versions are not allowed in group `{}`",
+                package.name.group()
+            ));
+        }
+
+        let max_upload_size = self.max_upload_size(group, usize::max_value());
+        if candidate.tarball_size > max_upload_size {
+            violations.push(format!(
+                "tarball is over {} bytes, the limit of group `{}`",
+                max_upload_size,
+                package.name.group()
+            ));
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elba::package::manifest::Manifest;
+
+    use super::*;
+    use crate::util::license::parse_license;
+
+    fn package(fields: &str) -> PackageInfo {
+        package_version("1.0.0", fields)
+    }
+
+    fn package_version(version: &str, fields: &str) -> PackageInfo {
+        let manifest = format!(
+            r#"
+            [package]
+            name = "group/package"
+            version = "{}"
+            authors = ["author"]
+            {}
+
+            [targets.lib]
+            path = "src/"
+            mods = ["Package"]
+            "#,
+            version, fields
+        );
+
+        Manifest::from_str(&manifest).unwrap().package
+    }
+
+    fn candidate(package: &PackageInfo) -> PublishCandidate {
+        PublishCandidate {
+            package,
+            license: None,
+            has_readme: false,
+            tarball_size: 1024,
+        }
+    }
+
+    #[test]
+    fn default_policy_allows_plain_packages() {
+        let package = package("");
+        let policy = PublishPolicy::default();
+
+        assert!(policy.violations(&candidate(&package)).is_empty());
+        assert!(policy.check(&candidate(&package)).is_ok());
+    }
+
+    #[test]
+    fn max_upload_size_falls_back_to_default() {
+        let mut policy = PublishPolicy::default();
+        policy
+            .group_max_upload_size
+            .insert("big-group".to_owned(), 4096);
+
+        assert_eq!(policy.max_upload_size("big-group", 1024), 4096);
+        assert_eq!(policy.max_upload_size("group", 1024), 1024);
+    }
+
+    #[test]
+    fn group_max_upload_size() {
+        let package = package("");
+        let mut policy = PublishPolicy::default();
+        policy.group_max_upload_size.insert("group".to_owned(), 512);
+
+        let violations = policy.violations(&candidate(&package));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("512"));
+
+        policy.group_max_upload_size.insert("group".to_owned(), 1024);
+        assert!(policy.violations(&candidate(&package)).is_empty());
+    }
+
+    #[test]
+    fn required_fields() {
+        let policy = PublishPolicy {
+            require_license: true,
+            require_repository: true,
+            require_readme: true,
+            ..PublishPolicy::default()
+        };
+
+        // every violation is reported at once
+        let bare = package("");
+        assert_eq!(policy.violations(&candidate(&bare)).len(), 3);
+        assert!(policy.check(&candidate(&bare)).is_err());
+
+        let complete = package(
+            r#"
+            license = "MIT"
+            repository = "https://github.com/group/package"
+            "#,
+        );
+        let license = parse_license("MIT").unwrap();
+        let candidate = PublishCandidate {
+            license: Some(&license),
+            has_readme: true,
+            ..candidate(&complete)
+        };
+        assert!(policy.violations(&candidate).is_empty());
+    }
+
+    #[test]
+    fn allowed_licenses() {
+        let package = package(r#"license = "MIT OR GPL-3.0-only""#);
+        let policy = PublishPolicy {
+            allowed_licenses: Some(vec!["MIT".to_owned(), "Apache-2.0".to_owned()]),
+            ..PublishPolicy::default()
+        };
+
+        let license = parse_license("MIT OR GPL-3.0-only").unwrap();
+        let violations = policy.violations(&PublishCandidate {
+            license: Some(&license),
+            ..candidate(&package)
+        });
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("GPL-3.0-only"));
+
+        let license = parse_license("MIT OR Apache-2.0").unwrap();
+        assert!(policy
+            .violations(&PublishCandidate {
+                license: Some(&license),
+                ..candidate(&package)
+            }).is_empty());
+
+        // packages without a license only violate `require_license`
+        assert!(policy.violations(&candidate(&package)).is_empty());
+    }
+
+    #[test]
+    fn license_refs_must_be_allowed_explicitly() {
+        let package = package(r#"license = "LicenseRef-Proprietary""#);
+        let license = parse_license("LicenseRef-Proprietary").unwrap();
+        let candidate = PublishCandidate {
+            license: Some(&license),
+            ..candidate(&package)
+        };
+
+        let policy = PublishPolicy {
+            allowed_licenses: Some(vec!["MIT".to_owned()]),
+            ..PublishPolicy::default()
+        };
+        let violations = policy.violations(&candidate);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("LicenseRef-Proprietary"));
+
+        let policy = PublishPolicy {
+            allowed_licenses: Some(vec![
+                "MIT".to_owned(),
+                "LicenseRef-Proprietary".to_owned(),
+            ]),
+            ..PublishPolicy::default()
+        };
+        assert!(policy.violations(&candidate).is_empty());
+
+        // without an allow-list, references are fine
+        assert!(PublishPolicy::default().violations(&candidate).is_empty());
+    }
+
+    #[test]
+    fn no_prerelease_groups() {
+        let policy = PublishPolicy {
+            no_prerelease_groups: vec!["group".to_owned()],
+            ..PublishPolicy::default()
+        };
+
+        let prerelease = package_version("1.0.0-beta.1", "");
+        let violations = policy.violations(&candidate(&prerelease));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("pre-release"));
+
+        // releases of the group and pre-releases of other groups are fine
+        assert!(policy.violations(&candidate(&package(""))).is_empty());
+        let other_group = PublishPolicy {
+            no_prerelease_groups: vec!["other-group".to_owned()],
+            ..PublishPolicy::default()
+        };
+        assert!(other_group.violations(&candidate(&prerelease)).is_empty());
+    }
+}
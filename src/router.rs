@@ -28,6 +28,12 @@ pub fn router(cors: &mut CorsBuilder<AppState>) {
     }).resource("/api/v1/users/tokens/{token_id}", |r| {
         r.delete()
             .with(compat(controller::users::token::remove_token))
+    }).resource("/api/v1/authors/{name}/packages", |r| {
+        r.get()
+            .with(compat(controller::packages::metadata::list_author_versions))
+    }).resource("/api/v1/licenses/{license}/packages", |r| {
+        r.get()
+            .with(compat(controller::packages::metadata::list_license_packages))
     }).resource("/api/v1/packages/search", |r| {
         r.get().with(compat(controller::packages::search))
     }).resource("/api/v1/packages/global_stats", |r| {
@@ -58,11 +64,23 @@ pub fn router(cors: &mut CorsBuilder<AppState>) {
         r.get()
             .with(compat(controller::packages::metadata::show_readme))
     }).resource(
+        "/api/v1/packages/{group}/{package}/{version}/manifest",
+        |r| {
+            r.get()
+                .with(compat(controller::packages::metadata::show_manifest))
+        },
+    ).resource(
         "/api/v1/packages/{group}/{package}/{version}/dependencies",
         |r| {
             r.get()
                 .with(compat(controller::packages::metadata::list_dependencies))
         },
+    ).resource("/api/v1/packages/{group}/{package}/{version}/files", |r| {
+        r.get()
+            .with(compat(controller::packages::files::list_files))
+    }).resource(
+        "/api/v1/packages/{group}/{package}/{version}/files/{path:.+}",
+        |r| r.get().with(compat(controller::packages::files::show_file)),
     ).resource(
         "/api/v1/packages/{group}/{package}/{version}/download",
         |r| {
@@ -74,6 +92,12 @@ pub fn router(cors: &mut CorsBuilder<AppState>) {
     }).resource(
         "/api/v1/packages/{group}/{package}/{version}/publish",
         |r| r.put().with(compat(controller::packages::publish)),
+    ).resource(
+        "/api/v1/packages/{group}/{package}/{version}/side_effects",
+        |r| {
+            r.get()
+                .with(compat(controller::packages::metadata::list_side_effects))
+        },
     ).resource(
         "/api/v1/packages/{group}/{package}/{version}/download_stats",
         |r| {
@@ -86,5 +110,11 @@ pub fn router(cors: &mut CorsBuilder<AppState>) {
             r.get()
                 .with(compat(controller::packages::download::download_graph))
         },
-    );
+    ).resource("/api/v1/admin/storage/check", |r| {
+        r.post().with(compat(controller::admin::check_storage))
+    }).resource("/api/v1/admin/storage/metrics", |r| {
+        r.get().with(compat(controller::admin::storage_metrics))
+    }).resource("/storage/{dir:tarballs|blobs|manifests|readmes}/{file}", |r| {
+        r.get().with(compat(controller::storage::serve_object))
+    });
 }
@@ -12,8 +12,12 @@ table! {
     dependencies (id) {
         id -> Int4,
         version_id -> Int4,
-        package_id -> Int4,
+        package_id -> Nullable<Int4>,
         version_req -> Varchar,
+        kind -> Varchar,
+        foreign_index -> Nullable<Varchar>,
+        foreign_group -> Nullable<Varchar>,
+        foreign_name -> Nullable<Varchar>,
     }
 }
 
@@ -27,6 +31,22 @@ table! {
     }
 }
 
+table! {
+    outbox_events (id) {
+        id -> Int4,
+        version_id -> Int4,
+        kind -> Varchar,
+        payload -> Nullable<Jsonb>,
+        tarball_path -> Nullable<Varchar>,
+        status -> Varchar,
+        attempts -> Int4,
+        last_error -> Nullable<Varchar>,
+        next_attempt_at -> Timestamp,
+        created_at -> Timestamp,
+        processed_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     package_owners (id) {
         id -> Int4,
@@ -75,6 +95,24 @@ table! {
     }
 }
 
+table! {
+    version_files (id) {
+        id -> Int4,
+        version_id -> Int4,
+        path -> Varchar,
+        size -> Int8,
+        mode -> Int4,
+    }
+}
+
+table! {
+    version_licenses (id) {
+        id -> Int4,
+        version_id -> Int4,
+        license -> Varchar,
+    }
+}
+
 table! {
     version_keywords (id) {
         id -> Int4,
@@ -94,6 +132,9 @@ table! {
         repository -> Nullable<Varchar>,
         license -> Nullable<Varchar>,
         created_at -> Timestamp,
+        checksum -> Nullable<Varchar>,
+        tarball_digest -> Nullable<Varchar>,
+        has_readme -> Bool,
     }
 }
 
@@ -101,23 +142,29 @@ joinable!(access_tokens -> users (user_id));
 joinable!(dependencies -> packages (package_id));
 joinable!(dependencies -> versions (version_id));
 joinable!(groups -> users (user_id));
+joinable!(outbox_events -> versions (version_id));
 joinable!(package_owners -> packages (package_id));
 joinable!(package_owners -> users (user_id));
 joinable!(packages -> groups (group_id));
 joinable!(version_authors -> versions (version_id));
 joinable!(version_downloads -> versions (version_id));
+joinable!(version_files -> versions (version_id));
 joinable!(version_keywords -> versions (version_id));
+joinable!(version_licenses -> versions (version_id));
 joinable!(versions -> packages (package_id));
 
 allow_tables_to_appear_in_same_query!(
     access_tokens,
     dependencies,
     groups,
+    outbox_events,
     package_owners,
     packages,
     users,
     version_authors,
     version_downloads,
+    version_files,
     version_keywords,
+    version_licenses,
     versions,
 );
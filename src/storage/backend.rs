@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use failure::Error;
+
+/// A place to keep the objects of the registry.
+///
+/// Objects are addressed by relative paths such as
+/// `tarballs/group_name_1.0.0.tar.gz`; backends decide how to map them.
+///
+/// The storage actor runs on several threads sharing one backend, so
+/// operations take `&self` and may be called concurrently.
+pub trait StorageBackend: Send + Sync {
+    fn store_object(&self, path: &str, bytes: Vec<u8>) -> Result<(), Error>;
+
+    /// Stores the whole content of `file`. Backends that can stream should
+    /// override this, the default reads the file into memory.
+    fn store_file(&self, path: &str, mut file: File) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut bytes)?;
+        self.store_object(path, bytes)
+    }
+
+    fn fetch_object(&self, path: &str) -> Result<File, Error>;
+
+    fn delete_object(&self, path: &str) -> Result<(), Error>;
+
+    /// Lists the paths of all objects under a directory such as `tarballs/`.
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, Error>;
+}
+
+/// A failure that may go away by itself, such as a timeout or a server
+/// error of a remote store. Backends return it to have the operation
+/// retried, every other error fails at once.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", _0)]
+pub struct Transient(pub String);
+
+pub fn is_transient(error: &Error) -> bool {
+    error.downcast_ref::<Transient>().is_some()
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use failure::{Error, ResultExt};
+use tempfile::NamedTempFile;
+
+use super::metrics::METRICS;
+use super::{StorageBackend, OBJECT_DIRS};
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub dir: PathBuf,
+    // in bytes
+    pub max_size: u64,
+}
+
+/// Keeps fetched objects on the local disk in front of a remote backend,
+/// evicting the least recently used ones once the cache is over its size.
+///
+/// Writes and deletes go straight to the inner backend and invalidate the
+/// cached copy. Cached objects survive restarts.
+pub struct CachedBackend {
+    inner: Box<dyn StorageBackend>,
+    config: CacheConfig,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    // path to size and the tick it was last used at
+    objects: HashMap<String, (u64, u64)>,
+    // tick to path, oldest first
+    lru: BTreeMap<u64, String>,
+    size: u64,
+    tick: u64,
+}
+
+impl CachedBackend {
+    pub fn new(inner: Box<dyn StorageBackend>, config: CacheConfig) -> Result<Self, Error> {
+        let mut found = Vec::new();
+
+        for dir in OBJECT_DIRS.iter() {
+            let dir_path = config.dir.join(dir);
+            fs::create_dir_all(&dir_path)
+                .with_context(|_| format!("can not create cache dir `{:?}`", &dir_path))?;
+
+            for entry in fs::read_dir(&dir_path)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // leftovers of interrupted downloads
+                if name.starts_with('.') {
+                    fs::remove_file(entry.path())?;
+                    continue;
+                }
+                if metadata.is_file() {
+                    found.push((format!("{}{}", dir, name), metadata));
+                }
+            }
+        }
+
+        // objects used last before the restart are evicted last
+        found.sort_by_key(|(_, metadata)| {
+            metadata
+                .accessed()
+                .or_else(|_| metadata.modified())
+                .ok()
+        });
+
+        let mut entries = CacheEntries::default();
+        for (path, metadata) in found {
+            entries.insert(path, metadata.len());
+        }
+
+        let cache = CachedBackend {
+            inner,
+            config,
+            entries: Mutex::new(entries),
+        };
+        cache.evict()?;
+
+        Ok(cache)
+    }
+
+    fn evict(&self) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+
+        while entries.size > self.config.max_size {
+            let path = match entries.lru.values().next() {
+                Some(path) => path.clone(),
+                None => break,
+            };
+
+            debug!("Storage cache: evicting `{}`", &path);
+            entries.remove(&path);
+            METRICS.record_cache_eviction();
+
+            match fs::remove_file(self.config.dir.join(&path)) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                result => result?,
+            }
+        }
+
+        METRICS.set_cache_size(entries.size);
+
+        Ok(())
+    }
+
+    fn invalidate(&self, path: &str) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(path);
+        METRICS.set_cache_size(entries.size);
+
+        match fs::remove_file(self.config.dir.join(path)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+impl StorageBackend for CachedBackend {
+    fn store_object(&self, path: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        self.invalidate(path)?;
+        self.inner.store_object(path, bytes)
+    }
+
+    fn store_file(&self, path: &str, file: File) -> Result<(), Error> {
+        self.invalidate(path)?;
+        self.inner.store_file(path, file)
+    }
+
+    fn fetch_object(&self, path: &str) -> Result<File, Error> {
+        let cache_path = self.config.dir.join(path);
+
+        if self.entries.lock().unwrap().touch(path) {
+            match File::open(&cache_path) {
+                Ok(file) => {
+                    METRICS.record_cache_hit();
+                    return Ok(file);
+                }
+                // removed behind our back
+                Err(_) => self.invalidate(path)?,
+            }
+        }
+
+        METRICS.record_cache_miss();
+        let mut file = self.inner.fetch_object(path)?;
+        let size = file.metadata()?.len();
+
+        if size > self.config.max_size {
+            return Ok(file);
+        }
+
+        // Downloads land in a hidden temporary file first, so that other
+        // threads never see a partial object.
+        let parent = cache_path
+            .parent()
+            .ok_or_else(|| format_err!("invalid object path `{}`", path))?;
+        let mut temp = NamedTempFile::new_in(parent)?;
+        file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut file, &mut temp)?;
+        temp.persist(&cache_path)?;
+
+        self.entries.lock().unwrap().insert(path.to_owned(), size);
+        self.evict()?;
+
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+
+    fn delete_object(&self, path: &str) -> Result<(), Error> {
+        self.invalidate(path)?;
+        self.inner.delete_object(path)
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        self.inner.list_objects(prefix)
+    }
+}
+
+impl CacheEntries {
+    fn insert(&mut self, path: String, size: u64) {
+        self.remove(&path);
+
+        self.tick += 1;
+        self.size += size;
+        self.lru.insert(self.tick, path.clone());
+        self.objects.insert(path, (size, self.tick));
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some((size, tick)) = self.objects.remove(path) {
+            self.size -= size;
+            self.lru.remove(&tick);
+        }
+    }
+
+    /// Marks an object as used, returns `false` if it is not cached.
+    fn touch(&mut self, path: &str) -> bool {
+        let tick = self.tick + 1;
+
+        match self.objects.get_mut(path) {
+            Some((_, last_used)) => {
+                self.lru.remove(&*last_used);
+                *last_used = tick;
+            }
+            None => return false,
+        }
+
+        self.tick = tick;
+        self.lru.insert(tick, path.to_owned());
+        true
+    }
+}
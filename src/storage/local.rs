@@ -0,0 +1,72 @@
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use failure::Error;
+
+use super::StorageBackend;
+
+/// Stores objects in a directory of the local filesystem.
+pub struct LocalBackend {
+    path: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(path: PathBuf) -> Result<Self, Error> {
+        fs::create_dir_all(path.join("tarballs"))?;
+        fs::create_dir_all(path.join("blobs"))?;
+        fs::create_dir_all(path.join("manifests"))?;
+        fs::create_dir_all(path.join("readmes"))?;
+
+        Ok(LocalBackend { path })
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    fn store_object(&self, path: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        let local_path = self.path.join(path);
+        info!("Local storage: saving object to `{:?}`", &local_path);
+        let mut file = File::create(&local_path)?;
+        file.write_all(&bytes)?;
+
+        Ok(())
+    }
+
+    fn store_file(&self, path: &str, mut file: File) -> Result<(), Error> {
+        file.seek(SeekFrom::Start(0))?;
+
+        let local_path = self.path.join(path);
+        info!("Local storage: saving object to `{:?}`", &local_path);
+        let mut local_file = File::create(&local_path)?;
+        io::copy(&mut file, &mut local_file)?;
+
+        Ok(())
+    }
+
+    fn fetch_object(&self, path: &str) -> Result<File, Error> {
+        let local_path = self.path.join(path);
+        info!("Local storage: reading object `{:?}`", &local_path);
+
+        Ok(File::open(&local_path)?)
+    }
+
+    fn delete_object(&self, path: &str) -> Result<(), Error> {
+        info!("Local storage: deleting object `{:?}`", &path);
+        fs::remove_file(self.path.join(path))?;
+
+        Ok(())
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut paths = Vec::new();
+
+        for entry in fs::read_dir(self.path.join(prefix))? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(format!("{}{}", prefix, entry.file_name().to_string_lossy()));
+            }
+        }
+
+        Ok(paths)
+    }
+}
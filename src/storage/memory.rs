@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+use failure::Error;
+
+use super::StorageBackend;
+
+/// Keeps objects in memory. Nothing survives a restart, which makes it
+/// handy for trying out the registry and for testing.
+#[derive(Default)]
+pub struct MemoryBackend {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend::default()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn store_object(&self, path: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        debug!("Memory storage: saving object to `{}`", path);
+        self.objects
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), bytes);
+
+        Ok(())
+    }
+
+    fn fetch_object(&self, path: &str) -> Result<File, Error> {
+        let objects = self.objects.lock().unwrap();
+        let bytes = objects
+            .get(path)
+            .ok_or_else(|| format_err!("object `{}` not found", path))?;
+
+        let mut file = tempfile::tempfile()?;
+        file.write_all(bytes)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(file)
+    }
+
+    fn delete_object(&self, path: &str) -> Result<(), Error> {
+        self.objects
+            .lock()
+            .unwrap()
+            .remove(path)
+            .ok_or_else(|| format_err!("object `{}` not found", path))?;
+
+        Ok(())
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
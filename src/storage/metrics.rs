@@ -0,0 +1,153 @@
+//! Counters of storage operations, shared by every storage thread.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+lazy_static! {
+    pub static ref METRICS: StorageMetrics = StorageMetrics::default();
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Store,
+    Fetch,
+    Delete,
+    List,
+}
+
+#[derive(Default)]
+pub struct StorageMetrics {
+    store: OperationMetrics,
+    fetch: OperationMetrics,
+    delete: OperationMetrics,
+    list: OperationMetrics,
+    cache: CacheMetrics,
+}
+
+#[derive(Default)]
+struct CacheMetrics {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    evictions: AtomicUsize,
+    size: AtomicUsize,
+}
+
+#[derive(Default)]
+struct OperationMetrics {
+    attempts: AtomicUsize,
+    retries: AtomicUsize,
+    failures: AtomicUsize,
+    latency_ms_total: AtomicUsize,
+    latency_ms_max: AtomicUsize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct OperationSnapshot {
+    pub operation: Operation,
+    pub attempts: usize,
+    pub retries: usize,
+    // operations that failed after the last retry
+    pub failures: usize,
+    pub latency_ms_total: usize,
+    pub latency_ms_max: usize,
+}
+
+/// Counters of the disk cache, which stay at zero if it is disabled.
+#[derive(Serialize, Clone)]
+pub struct CacheSnapshot {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    // bytes currently cached
+    pub size: usize,
+}
+
+impl StorageMetrics {
+    /// Records a single attempt of an operation.
+    pub fn record_attempt(&self, operation: Operation, latency: Duration) {
+        let metrics = self.of(operation);
+        let latency_ms = latency.as_secs() as usize * 1000 + latency.subsec_millis() as usize;
+
+        metrics.attempts.fetch_add(1, Ordering::Relaxed);
+        metrics
+            .latency_ms_total
+            .fetch_add(latency_ms, Ordering::Relaxed);
+
+        let mut max = metrics.latency_ms_max.load(Ordering::Relaxed);
+        while latency_ms > max {
+            match metrics.latency_ms_max.compare_exchange_weak(
+                max,
+                latency_ms,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => max = current,
+            }
+        }
+    }
+
+    pub fn record_retry(&self, operation: Operation) {
+        self.of(operation).retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, operation: Operation) {
+        self.of(operation).failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_hit(&self) {
+        self.cache.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_miss(&self) {
+        self.cache.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_eviction(&self) {
+        self.cache.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_cache_size(&self, size: u64) {
+        self.cache.size.store(size as usize, Ordering::Relaxed);
+    }
+
+    pub fn cache_snapshot(&self) -> CacheSnapshot {
+        CacheSnapshot {
+            hits: self.cache.hits.load(Ordering::Relaxed),
+            misses: self.cache.misses.load(Ordering::Relaxed),
+            evictions: self.cache.evictions.load(Ordering::Relaxed),
+            size: self.cache.size.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<OperationSnapshot> {
+        [
+            Operation::Store,
+            Operation::Fetch,
+            Operation::Delete,
+            Operation::List,
+        ]
+            .iter()
+            .map(|&operation| {
+                let metrics = self.of(operation);
+                OperationSnapshot {
+                    operation,
+                    attempts: metrics.attempts.load(Ordering::Relaxed),
+                    retries: metrics.retries.load(Ordering::Relaxed),
+                    failures: metrics.failures.load(Ordering::Relaxed),
+                    latency_ms_total: metrics.latency_ms_total.load(Ordering::Relaxed),
+                    latency_ms_max: metrics.latency_ms_max.load(Ordering::Relaxed),
+                }
+            }).collect()
+    }
+
+    fn of(&self, operation: Operation) -> &OperationMetrics {
+        match operation {
+            Operation::Store => &self.store,
+            Operation::Fetch => &self.fetch,
+            Operation::Delete => &self.delete,
+            Operation::List => &self.list,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Copies every object from one backend to another.
+//!
+//! The registry can keep serving from the source while objects are being
+//! copied. Every verified object is recorded in a journal file along with
+//! its checksum, so running the migration again only copies objects that
+//! failed, were published or were rewritten in the meantime, and deletes
+//! copies of objects that are gone from the source.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use failure::{Error, ResultExt};
+use sha2::{Digest, Sha256};
+
+use super::metrics::Operation;
+use super::retry::{attempt, RetryPolicy};
+use super::{StorageBackend, OBJECT_DIRS};
+
+// marks a removed copy in the journal
+const REMOVED: &str = "-";
+
+#[derive(Default)]
+pub struct MigrationReport {
+    pub copied: usize,
+    // objects recorded in the journal by an earlier run and unchanged since
+    pub skipped: usize,
+    // copies deleted since their objects are gone from the source
+    pub removed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+pub fn migrate(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    retry: &RetryPolicy,
+    journal_path: &Path,
+) -> Result<MigrationReport, Error> {
+    let mut done = read_journal(journal_path)?;
+    let mut journal = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path)
+        .with_context(|_| format!("can not open journal `{:?}`", journal_path))?;
+
+    let mut paths = Vec::new();
+    for dir in OBJECT_DIRS.iter() {
+        paths.extend(attempt(retry, Operation::List, dir, || {
+            source.list_objects(dir)
+        })?);
+    }
+    paths.sort();
+
+    let total = paths.len();
+    let mut report = MigrationReport::default();
+
+    for (index, path) in paths.iter().enumerate() {
+        let journaled = done.remove(path);
+
+        match copy_object(source, target, retry, path, journaled.as_ref()) {
+            Ok(None) => report.skipped += 1,
+            Ok(Some((checksum, size))) => {
+                writeln!(journal, "{} {} {}", checksum, size, path)?;
+                journal.sync_data()?;
+
+                report.copied += 1;
+                println!("[{}/{}] copied `{}` ({} bytes)", index + 1, total, path, size);
+            }
+            Err(err) => {
+                report.failed.push(path.clone());
+                println!("[{}/{}] failed `{}`: {}", index + 1, total, path, err);
+            }
+        }
+    }
+
+    // What is left in the journal was copied before and has been deleted
+    // from the source since, e.g. by the orphan collector.
+    let mut removed: Vec<String> = done.into_iter().map(|(path, _)| path).collect();
+    removed.sort();
+
+    for path in removed {
+        match attempt(retry, Operation::Delete, &path, || target.delete_object(&path)) {
+            Ok(()) => {
+                writeln!(journal, "{} {} {}", REMOVED, REMOVED, path)?;
+                journal.sync_data()?;
+
+                println!("removed `{}`, which is gone from the source", path);
+                report.removed.push(path);
+            }
+            Err(err) => {
+                println!("failed to remove `{}`: {}", path, err);
+                report.failed.push(path);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Copies an object and reads it back from the target to make sure it
+/// arrived intact. Returns the checksum and size of the object, or `None`
+/// if it matches what the journal recorded and was not copied again.
+fn copy_object(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    retry: &RetryPolicy,
+    path: &str,
+    journaled: Option<&(String, u64)>,
+) -> Result<Option<(String, u64)>, Error> {
+    let mut file = attempt(retry, Operation::Fetch, path, || source.fetch_object(path))?;
+    let (checksum, size) = digest(&mut file)?;
+
+    if journaled == Some(&(checksum.clone(), size)) {
+        return Ok(None);
+    }
+
+    attempt(retry, Operation::Store, path, || {
+        target.store_file(path, file.try_clone()?)
+    })?;
+
+    let mut copied = attempt(retry, Operation::Fetch, path, || target.fetch_object(path))?;
+    let (copied_checksum, copied_size) = digest(&mut copied)?;
+
+    if copied_size != size || copied_checksum != checksum {
+        bail!(
+            "copy does not match the source (expected {} bytes with sha256 {}, found {} bytes with sha256 {})",
+            size,
+            checksum,
+            copied_size,
+            copied_checksum
+        );
+    }
+
+    Ok(Some((checksum, size)))
+}
+
+fn digest(file: &mut File) -> Result<(String, u64), Error> {
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut hasher = Sha256::default();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let len = file.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hasher.input(&buffer[..len]);
+        size += len as u64;
+    }
+
+    Ok((format!("{:x}", hasher.result()), size))
+}
+
+/// Reads the objects recorded by earlier runs along with their checksum
+/// and size. Each line of the journal is `<sha256> <size> <path>`, or
+/// `- - <path>` for a removed copy. Later lines win.
+fn read_journal(journal_path: &Path) -> Result<HashMap<String, (String, u64)>, Error> {
+    let mut done = HashMap::new();
+
+    if !journal_path.exists() {
+        return Ok(done);
+    }
+
+    for line in BufReader::new(File::open(journal_path)?).lines() {
+        let line = line?;
+        let mut fields = line.splitn(3, ' ');
+
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(REMOVED), Some(REMOVED), Some(path)) => {
+                done.remove(path);
+            }
+            (Some(checksum), Some(size), Some(path)) => {
+                if let Ok(size) = size.parse() {
+                    done.insert(path.to_owned(), (checksum.to_owned(), size));
+                }
+            }
+            // e.g. cut off by a crash, the object is simply copied again
+            _ => {}
+        }
+    }
+
+    Ok(done)
+}
@@ -0,0 +1,518 @@
+mod backend;
+mod cache;
+mod local;
+mod memory;
+mod migrate;
+mod retry;
+mod s3;
+
+pub mod metrics;
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::prelude::*;
+use bytes::Bytes;
+use failure::Error;
+use rusoto_core::Region;
+
+use crate::model::packages::PackageVersion;
+use crate::CONFIG;
+
+pub use self::backend::{is_transient, StorageBackend, Transient};
+pub use self::cache::{CacheConfig, CachedBackend};
+pub use self::local::LocalBackend;
+pub use self::memory::MemoryBackend;
+pub use self::migrate::{migrate, MigrationReport};
+pub use self::retry::RetryPolicy;
+pub use self::s3::S3Backend;
+
+use self::metrics::Operation;
+
+#[derive(Debug, Clone)]
+pub enum StorageConfig {
+    Local {
+        // path to store packages locally
+        path: PathBuf,
+        // url to retrieve local pacakages from public network,
+        // `None` if the registry serves them itself
+        url: Option<String>,
+    },
+    S3 {
+        bucket: String,
+        access_key: String,
+        secret_key: String,
+        region: Region,
+        base_url: String,
+        // `Cache-Control` of uploaded objects
+        cache_control: Option<String>,
+        // timeout of a single request
+        timeout: Duration,
+    },
+}
+
+// Directories that hold the objects of the registry.
+pub const OBJECT_DIRS: [&str; 4] = ["tarballs/", "blobs/", "manifests/", "readmes/"];
+
+/// Runs storage operations on a pool of threads, so that a slow upload
+/// does not hold back other messages. Packages are uploaded by a separate
+/// pool of `CONFIG.storage_upload_threads`, which keeps publishing from
+/// taking every thread downloads need. Transient failures are retried
+/// according to `CONFIG.storage_retry`.
+#[derive(Clone)]
+pub struct Storage {
+    backend: Arc<dyn StorageBackend>,
+    retry: RetryPolicy,
+}
+
+impl Actor for Storage {
+    type Context = SyncContext<Self>;
+}
+
+pub struct StorePackage {
+    pub package: PackageVersion,
+    // the tarball is stored content-addressed if this is set
+    pub digest: Option<String>,
+    pub tarball: File,
+    pub manifest: String,
+    pub readme: Option<String>,
+    pub readme_html: Option<String>,
+}
+
+impl Message for StorePackage {
+    type Result = Result<StorageTransaction, Error>;
+}
+
+pub struct StoreObject {
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+pub struct DeleteObject {
+    pub path: String,
+}
+
+pub struct ListObjects {
+    pub prefix: String,
+}
+
+pub struct FetchTarball {
+    pub package: PackageVersion,
+    pub digest: Option<String>,
+}
+
+/// Appends a chunk of an upload to its temporary file.
+pub struct WriteChunk {
+    pub file: File,
+    pub chunk: Bytes,
+}
+
+impl Message for StoreObject {
+    type Result = Result<(), Error>;
+}
+
+impl Message for DeleteObject {
+    type Result = Result<(), Error>;
+}
+
+impl Message for ListObjects {
+    type Result = Result<Vec<String>, Error>;
+}
+
+impl Message for FetchTarball {
+    type Result = Result<File, Error>;
+}
+
+impl Message for WriteChunk {
+    type Result = Result<File, Error>;
+}
+
+impl Storage {
+    pub fn new() -> Result<Self, Error> {
+        let backend: Box<dyn StorageBackend> = match &CONFIG.storage_cache {
+            Some(cache) => Box::new(CachedBackend::new(
+                open_backend(&CONFIG.storage_config)?,
+                cache.clone(),
+            )?),
+            None => open_backend(&CONFIG.storage_config)?,
+        };
+
+        Ok(Storage::with_backend(backend, CONFIG.storage_retry.clone()))
+    }
+
+    pub fn with_backend(backend: Box<dyn StorageBackend>, retry: RetryPolicy) -> Self {
+        Storage {
+            backend: Arc::from(backend),
+            retry,
+        }
+    }
+
+    /// Runs an operation on the backend, retrying it as long as it
+    /// fails transiently.
+    fn attempt<T>(
+        &self,
+        operation: Operation,
+        path: &str,
+        mut f: impl FnMut(&dyn StorageBackend) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        retry::attempt(&self.retry, operation, path, || f(&*self.backend))
+    }
+}
+
+pub fn open_backend(config: &StorageConfig) -> Result<Box<dyn StorageBackend>, Error> {
+    let backend: Box<dyn StorageBackend> = match config {
+        StorageConfig::Local { path, .. } => Box::new(LocalBackend::new(path.clone())?),
+        StorageConfig::S3 {
+            bucket,
+            access_key,
+            secret_key,
+            region,
+            cache_control,
+            timeout,
+            ..
+        } => Box::new(S3Backend::new(
+            bucket.clone(),
+            access_key.clone(),
+            secret_key.clone(),
+            region.clone(),
+            cache_control.clone(),
+            *timeout,
+        )?),
+    };
+
+    Ok(backend)
+}
+
+impl Handler<StorePackage> for Storage {
+    type Result = Result<StorageTransaction, Error>;
+
+    fn handle(&mut self, msg: StorePackage, ctx: &mut Self::Context) -> Self::Result {
+        let mut transaction = StorageTransaction::new(ctx.address());
+
+        // save tarball, every attempt reads the file from the start
+        if let Some(digest) = &msg.digest {
+            // Blobs are never rolled back, since a version published again
+            // with the same tarball shares its blob. Unused blobs are left
+            // to the orphan collector.
+            let blob_path = blob_path(digest);
+            self.attempt(Operation::Store, &blob_path, |backend| {
+                backend.store_file(&blob_path, msg.tarball.try_clone()?)
+            })?;
+        }
+        if msg.digest.is_none() || CONFIG.legacy_tarball_paths {
+            let tar_path = tarball_path(&msg.package);
+            self.attempt(Operation::Store, &tar_path, |backend| {
+                backend.store_file(&tar_path, msg.tarball.try_clone()?)
+            })?;
+            transaction.paths.push(tar_path);
+        }
+
+        // save manifest
+        let manifest_path = manifest_path(&msg.package);
+        let manifest = msg.manifest.into_bytes();
+        self.attempt(Operation::Store, &manifest_path, |backend| {
+            backend.store_object(&manifest_path, manifest.clone())
+        })?;
+        transaction.paths.push(manifest_path);
+
+        // save readme
+        if let Some(readme) = msg.readme {
+            let readme_path = readme_path(&msg.package);
+            let readme = readme.into_bytes();
+            self.attempt(Operation::Store, &readme_path, |backend| {
+                backend.store_object(&readme_path, readme.clone())
+            })?;
+            transaction.paths.push(readme_path);
+        }
+
+        // save rendered readme
+        if let Some(readme_html) = msg.readme_html {
+            let readme_html_path = readme_html_path(&msg.package);
+            let readme_html = readme_html.into_bytes();
+            self.attempt(Operation::Store, &readme_html_path, |backend| {
+                backend.store_object(&readme_html_path, readme_html.clone())
+            })?;
+            transaction.paths.push(readme_html_path);
+        }
+
+        Ok(transaction)
+    }
+}
+
+impl Handler<FetchTarball> for Storage {
+    type Result = Result<File, Error>;
+
+    fn handle(&mut self, msg: FetchTarball, _: &mut Self::Context) -> Self::Result {
+        let path = tarball_object_path(&msg.package, msg.digest.as_ref().map(|s| s.as_str()));
+        self.attempt(Operation::Fetch, &path, |backend| backend.fetch_object(&path))
+    }
+}
+
+impl Handler<WriteChunk> for Storage {
+    type Result = Result<File, Error>;
+
+    fn handle(&mut self, msg: WriteChunk, _: &mut Self::Context) -> Self::Result {
+        let mut file = msg.file;
+        file.write_all(&msg.chunk)?;
+        Ok(file)
+    }
+}
+
+impl Handler<StoreObject> for Storage {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: StoreObject, _: &mut Self::Context) -> Self::Result {
+        self.attempt(Operation::Store, &msg.path, |backend| {
+            backend.store_object(&msg.path, msg.bytes.clone())
+        })
+    }
+}
+
+impl Handler<DeleteObject> for Storage {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: DeleteObject, _: &mut Self::Context) -> Self::Result {
+        self.attempt(Operation::Delete, &msg.path, |backend| {
+            backend.delete_object(&msg.path)
+        })
+    }
+}
+
+impl Handler<ListObjects> for Storage {
+    type Result = Result<Vec<String>, Error>;
+
+    fn handle(&mut self, msg: ListObjects, _: &mut Self::Context) -> Self::Result {
+        self.attempt(Operation::List, &msg.prefix, |backend| {
+            backend.list_objects(&msg.prefix)
+        })
+    }
+}
+
+pub struct StorageTransaction {
+    storage: Addr<Storage>,
+    paths: Vec<String>,
+    commited: bool,
+}
+
+impl StorageTransaction {
+    pub fn new(storage: Addr<Storage>) -> Self {
+        StorageTransaction {
+            storage,
+            paths: Vec::new(),
+            commited: false,
+        }
+    }
+
+    pub fn commit(mut self) {
+        self.commited = true;
+    }
+}
+
+impl Drop for StorageTransaction {
+    fn drop(&mut self) {
+        if !self.commited {
+            info!("Rollback storage: {:?}", &self.paths);
+            for path in self.paths.iter().cloned() {
+                self.storage.do_send(DeleteObject { path });
+            }
+        }
+    }
+}
+
+/// The url of a tarball, resolved through the digest recorded for the
+/// version. Versions without a digest keep their original url.
+pub fn get_tarball_location(package: &PackageVersion, digest: Option<&str>) -> String {
+    format!("{}/{}", get_base_url(), &tarball_object_path(package, digest))
+}
+
+pub fn get_manifest_location(package: &PackageVersion) -> String {
+    format!("{}/{}", get_base_url(), &manifest_path(package))
+}
+
+pub fn get_readme_location(package: &PackageVersion) -> String {
+    format!("{}/{}", get_base_url(), &readme_path(package))
+}
+
+pub fn get_readme_html_location(package: &PackageVersion) -> String {
+    format!("{}/{}", get_base_url(), &readme_html_path(package))
+}
+
+/// The content type of a stored object, judging by its path.
+pub fn content_type(path: &str) -> &'static str {
+    if path.ends_with(".tar.gz") {
+        "application/gzip"
+    } else if path.ends_with(".md") {
+        "text/markdown; charset=utf-8"
+    } else if path.ends_with(".html") {
+        "text/html; charset=utf-8"
+    } else if path.ends_with(".toml") {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn get_base_url() -> String {
+    match &CONFIG.storage_config {
+        StorageConfig::Local { url: Some(url), .. } => url.clone(),
+        StorageConfig::Local { url: None, .. } => format!(
+            "{}/storage",
+            CONFIG.registry.url.as_str().trim_end_matches('/')
+        ),
+        StorageConfig::S3 { base_url, .. } => base_url.clone(),
+    }
+}
+
+pub fn tarball_path(package: &PackageVersion) -> String {
+    format!(
+        "tarballs/{}_{}_{}.tar.gz",
+        &package.name.normalized_group(),
+        &package.name.normalized_name(),
+        &package.semver
+    )
+}
+
+/// Where a content-addressed tarball is stored, keyed by its sha256.
+pub fn blob_path(digest: &str) -> String {
+    format!("blobs/{}.tar.gz", digest)
+}
+
+/// Where the tarball of a version is stored, depending on whether a
+/// digest is recorded for it.
+pub fn tarball_object_path(package: &PackageVersion, digest: Option<&str>) -> String {
+    match digest {
+        Some(digest) => blob_path(digest),
+        None => tarball_path(package),
+    }
+}
+
+pub fn readme_path(package: &PackageVersion) -> String {
+    format!(
+        "readmes/{}_{}_{}.md",
+        &package.name.normalized_group(),
+        &package.name.normalized_name(),
+        &package.semver
+    )
+}
+
+pub fn manifest_path(package: &PackageVersion) -> String {
+    format!(
+        "manifests/{}_{}_{}.toml",
+        &package.name.normalized_group(),
+        &package.name.normalized_name(),
+        &package.semver
+    )
+}
+
+pub fn readme_html_path(package: &PackageVersion) -> String {
+    format!(
+        "readmes/{}_{}_{}.html",
+        &package.name.normalized_group(),
+        &package.name.normalized_name(),
+        &package.semver
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use elba::package::Name as PackageName;
+
+    use super::*;
+
+    fn package() -> PackageVersion {
+        PackageVersion {
+            name: PackageName::new("group".to_owned(), "package".to_owned()).unwrap(),
+            semver: "1.0.0".parse().unwrap(),
+        }
+    }
+
+    fn tarball(data: &[u8]) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(data).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file
+    }
+
+    fn store_package() -> StorePackage {
+        StorePackage {
+            package: package(),
+            digest: None,
+            tarball: tarball(b"tarball"),
+            manifest: "manifest".to_owned(),
+            readme: Some("readme".to_owned()),
+            readme_html: Some("<p>readme</p>".to_owned()),
+        }
+    }
+
+    fn start() -> (SystemRunner, Addr<Storage>) {
+        let sys = System::new("test");
+        let storage = SyncArbiter::start(1, || {
+            Storage::with_backend(Box::new(MemoryBackend::new()), RetryPolicy::default())
+        });
+        (sys, storage)
+    }
+
+    fn list(sys: &mut SystemRunner, storage: &Addr<Storage>) -> Vec<String> {
+        let mut paths = sys
+            .block_on(storage.send(ListObjects { prefix: String::new() }))
+            .unwrap()
+            .unwrap();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn publish_stores_package_objects() {
+        let (mut sys, storage) = start();
+
+        let transaction = sys.block_on(storage.send(store_package())).unwrap().unwrap();
+        transaction.commit();
+
+        let package = package();
+        let mut expected = vec![
+            tarball_path(&package),
+            manifest_path(&package),
+            readme_path(&package),
+            readme_html_path(&package),
+        ];
+        expected.sort();
+        assert_eq!(list(&mut sys, &storage), expected);
+    }
+
+    #[test]
+    fn failed_publish_rolls_back_objects() {
+        let (mut sys, storage) = start();
+
+        let transaction = sys.block_on(storage.send(store_package())).unwrap().unwrap();
+        drop(transaction);
+
+        // with a single thread, the deletes are handled before the listing
+        assert!(list(&mut sys, &storage).is_empty());
+    }
+
+    #[test]
+    fn published_tarball_stays_downloadable() {
+        // Yanking only changes the index, so the tarball of a yanked
+        // version is fetched exactly like any other.
+        let (mut sys, storage) = start();
+
+        let transaction = sys.block_on(storage.send(store_package())).unwrap().unwrap();
+        transaction.commit();
+
+        let mut file = sys
+            .block_on(storage.send(FetchTarball {
+                package: package(),
+                digest: None,
+            }))
+            .unwrap()
+            .unwrap();
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"tarball");
+    }
+}
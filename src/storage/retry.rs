@@ -0,0 +1,72 @@
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::Error;
+
+use super::is_transient;
+use super::metrics::{Operation, METRICS};
+
+/// How often and how patiently transient storage failures are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // retries after the first attempt
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the given retry, doubling from `base_backoff`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32 << cmp::min(retry.saturating_sub(1), 16);
+        cmp::min(self.base_backoff * factor, self.max_backoff)
+    }
+}
+
+/// Runs a storage operation, retrying it with exponential backoff as long
+/// as it fails transiently. Attempts are recorded in the storage metrics.
+pub fn attempt<T>(
+    policy: &RetryPolicy,
+    operation: Operation,
+    path: &str,
+    mut f: impl FnMut() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut retries = 0;
+
+    loop {
+        let started = Instant::now();
+        let result = f();
+        METRICS.record_attempt(operation, started.elapsed());
+
+        match result {
+            Err(ref err) if is_transient(err) && retries < policy.max_retries => {
+                retries += 1;
+                let backoff = policy.backoff(retries);
+                warn!(
+                    "Storage: `{}` failed transiently, retry {} in {:?}: {}",
+                    path, retries, backoff, err
+                );
+
+                METRICS.record_retry(operation);
+                // this only blocks the calling storage thread
+                thread::sleep(backoff);
+            }
+            Err(err) => {
+                METRICS.record_failure(operation);
+                return Err(err);
+            }
+            Ok(value) => return Ok(value),
+        }
+    }
+}
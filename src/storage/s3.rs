@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use bytes::Bytes;
+use failure::{Error, ResultExt as _};
+use futures::{stream, Future, Stream};
+use rusoto_core::request::HttpClient;
+use rusoto_core::{ByteStream, Region};
+use rusoto_credential::StaticProvider;
+use rusoto_s3::{
+    DeleteObjectError, DeleteObjectRequest, GetObjectError, GetObjectRequest,
+    ListObjectsV2Error, ListObjectsV2Request, PutObjectError, PutObjectRequest, S3Client,
+    S3 as _,
+};
+
+use super::{content_type, StorageBackend, Transient};
+
+/// Converts the error of an S3 request, marking network failures,
+/// throttling and server errors as transient.
+macro_rules! s3_error {
+    ($error_type:ident) => {
+        |err: $error_type| -> Error {
+            match err {
+                $error_type::HttpDispatch(err) => Transient(err.to_string()).into(),
+                $error_type::Unknown(ref response)
+                    if response.status.is_server_error() || response.status.as_u16() == 429 =>
+                {
+                    Transient(format!("S3 responded with {}", response.status)).into()
+                }
+                err => err.into(),
+            }
+        }
+    };
+}
+
+/// Stores objects in an S3 bucket.
+pub struct S3Backend {
+    client: S3Client,
+    bucket: String,
+    cache_control: Option<String>,
+    // timeout of a single request
+    timeout: Duration,
+}
+
+impl S3Backend {
+    pub fn new(
+        bucket: String,
+        access_key: String,
+        secret_key: String,
+        region: Region,
+        cache_control: Option<String>,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let credential = StaticProvider::new_minimal(access_key, secret_key);
+
+        Ok(S3Backend {
+            client: S3Client::new_with(
+                HttpClient::new().with_context(|_| "can not setup S3 http client")?,
+                credential,
+                region,
+            ),
+            bucket,
+            cache_control,
+            timeout,
+        })
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn store_object(&self, path: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        info!("S3 storage: saving object to `{:?}`", &path);
+        let mut future = self.client.put_object(PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: path.to_owned(),
+            body: Some(ByteStream::from(bytes)),
+            content_type: Some(content_type(path).to_owned()),
+            cache_control: self.cache_control.clone(),
+            ..Default::default()
+        });
+        future.set_timeout(self.timeout);
+        future.sync().map_err(s3_error!(PutObjectError))?;
+
+        Ok(())
+    }
+
+    fn store_file(&self, path: &str, mut file: File) -> Result<(), Error> {
+        file.seek(SeekFrom::Start(0))?;
+
+        info!("S3 storage: saving object to `{:?}`", &path);
+        let content_length = file.metadata()?.len() as i64;
+        let mut future = self.client.put_object(PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: path.to_owned(),
+            body: Some(ByteStream::new(stream::iter_result(FileChunks(file)))),
+            content_length: Some(content_length),
+            content_type: Some(content_type(path).to_owned()),
+            cache_control: self.cache_control.clone(),
+            ..Default::default()
+        });
+        future.set_timeout(self.timeout);
+        future.sync().map_err(s3_error!(PutObjectError))?;
+
+        Ok(())
+    }
+
+    fn fetch_object(&self, path: &str) -> Result<File, Error> {
+        info!("S3 storage: reading object `{:?}`", &path);
+        let mut future = self.client.get_object(GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: path.to_owned(),
+            ..Default::default()
+        });
+        future.set_timeout(self.timeout);
+        let body = future
+            .sync()
+            .map_err(s3_error!(GetObjectError))?
+            .body
+            .ok_or_else(|| format_err!("object `{}` has no content", path))?;
+
+        let mut file = tempfile::tempfile()?;
+        // a connection dropped halfway is worth another try
+        body.for_each(|chunk| file.write_all(&chunk))
+            .wait()
+            .map_err(|err| Transient(format!("failed to read `{}`: {}", path, err)))?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(file)
+    }
+
+    fn delete_object(&self, path: &str) -> Result<(), Error> {
+        info!("S3 storage: deleting object `{:?}`", &path);
+        let mut future = self.client.delete_object(DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key: path.to_owned(),
+            ..Default::default()
+        });
+        future.set_timeout(self.timeout);
+        future.sync().map_err(s3_error!(DeleteObjectError))?;
+
+        Ok(())
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        info!("S3 storage: listing objects under `{:?}`", &prefix);
+        let mut paths = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let mut future = self.client.list_objects_v2(ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(prefix.to_owned()),
+                continuation_token: continuation_token.take(),
+                ..Default::default()
+            });
+            future.set_timeout(self.timeout);
+            let output = future.sync().map_err(s3_error!(ListObjectsV2Error))?;
+
+            paths.extend(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|object| object.key),
+            );
+
+            match output.next_continuation_token {
+                Some(token) if output.is_truncated == Some(true) => {
+                    continuation_token = Some(token)
+                }
+                _ => break,
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+/// Reads a file in chunks, so that it can be uploaded as a stream.
+struct FileChunks(File);
+
+impl Iterator for FileChunks {
+    type Item = Result<Bytes, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buffer = vec![0; 64 * 1024];
+        match self.0.read(&mut buffer) {
+            Ok(0) => None,
+            Ok(len) => {
+                buffer.truncate(len);
+                Some(Ok(Bytes::from(buffer)))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    /// A request as received by the S3 stand-in.
+    struct Request {
+        line: String,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Answers a single request with an empty `200 OK`, standing in for S3.
+    fn stand_in() -> (String, JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                let mut parts = header.splitn(2, ':');
+                let key = parts.next().unwrap().trim().to_owned();
+                let value = parts.next().unwrap_or("").trim().to_owned();
+                headers.push((key, value));
+            }
+
+            let mut request = Request {
+                line: line.trim_end().to_owned(),
+                headers,
+                body: Vec::new(),
+            };
+            let length = request
+                .header("content-length")
+                .map(|length| length.parse().unwrap())
+                .unwrap_or(0);
+            request.body.resize(length, 0);
+            reader.read_exact(&mut request.body).unwrap();
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+
+            request
+        });
+
+        (endpoint, handle)
+    }
+
+    fn backend(endpoint: String) -> S3Backend {
+        S3Backend::new(
+            "bucket".to_owned(),
+            "access".to_owned(),
+            "secret".to_owned(),
+            Region::Custom {
+                name: "us-east-1".to_owned(),
+                endpoint,
+            },
+            Some("public, max-age=31536000".to_owned()),
+            Duration::from_secs(10),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn stores_objects_on_custom_endpoint() {
+        let (endpoint, handle) = stand_in();
+
+        backend(endpoint)
+            .store_object("readmes/group_package_1.0.0.html", b"<p>readme</p>".to_vec())
+            .unwrap();
+        let request = handle.join().unwrap();
+
+        assert_eq!(
+            request.line,
+            "PUT /bucket/readmes/group_package_1.0.0.html HTTP/1.1"
+        );
+        assert_eq!(
+            request.header("content-type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            request.header("cache-control"),
+            Some("public, max-age=31536000")
+        );
+        assert_eq!(request.body, b"<p>readme</p>");
+    }
+
+    #[test]
+    fn streams_files_on_custom_endpoint() {
+        let (endpoint, handle) = stand_in();
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"tarball").unwrap();
+        backend(endpoint)
+            .store_file("blobs/digest.tar.gz", file)
+            .unwrap();
+        let request = handle.join().unwrap();
+
+        assert_eq!(request.line, "PUT /bucket/blobs/digest.tar.gz HTTP/1.1");
+        assert_eq!(request.header("content-type"), Some("application/gzip"));
+        assert_eq!(
+            request.header("cache-control"),
+            Some("public, max-age=31536000")
+        );
+        assert_eq!(request.header("content-length"), Some("7"));
+        assert_eq!(request.body, b"tarball");
+    }
+}
@@ -39,6 +39,8 @@ pub enum Reason {
     InvalidRequest,
     InvalidFormat,
     InvalidManifest,
+    InvalidArchive,
+    PolicyViolation,
     NoPermission,
     UserNotFound,
     TokenNotFound,
@@ -52,6 +54,8 @@ impl Reason {
             Reason::InvalidRequest => "invalid_request",
             Reason::InvalidFormat => "invalid_format",
             Reason::InvalidManifest => "invalid_manifest",
+            Reason::InvalidArchive => "invalid_archive",
+            Reason::PolicyViolation => "policy_violation",
             Reason::NoPermission => "no_permission",
             Reason::UserNotFound => "user_not_found",
             Reason::TokenNotFound => "token_not_found",
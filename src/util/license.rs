@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use failure::Error;
+
+use crate::util::error::Reason;
+
+lazy_static! {
+    static ref LICENSES: HashMap<String, &'static str> =
+        load_list(include_str!("spdx_licenses.txt"));
+    static ref EXCEPTIONS: HashMap<String, &'static str> =
+        load_list(include_str!("spdx_exceptions.txt"));
+}
+
+/// A license expression in normalized form, along with the SPDX license
+/// identifiers and the user defined references it mentions.
+#[derive(Debug, Clone)]
+pub struct License {
+    pub expression: String,
+    pub ids: Vec<String>,
+    // `LicenseRef-*` and `DocumentRef-*`
+    pub refs: Vec<String>,
+}
+
+/// Whether an identifier is a user defined license reference.
+pub fn is_license_ref(id: &str) -> bool {
+    id.starts_with("LicenseRef-") || id.starts_with("DocumentRef-")
+}
+
+/// Looks up a license identifier in the bundled SPDX license list, ignoring
+/// case, and returns its canonical spelling.
+pub fn canonical_id(id: &str) -> Option<&'static str> {
+    LICENSES.get(&id.to_lowercase()).cloned()
+}
+
+/// Parses an SPDX license expression such as `MIT OR Apache-2.0`.
+///
+/// Identifiers and operators are matched regardless of case and written back
+/// in their canonical spelling.
+pub fn parse_license(expression: &str) -> Result<License, Error> {
+    // a common mistake, which deserves a hint
+    if expression.contains(',') {
+        return Err(human!(
+            Reason::InvalidManifest,
+            "Invalid license expression `{}`: licenses are combined with `OR` or `AND`, not commas",
+            expression
+        ));
+    }
+
+    let mut parser = Parser {
+        source: expression,
+        tokens: tokenize(expression),
+        pos: 0,
+        ids: Vec::new(),
+        refs: Vec::new(),
+    };
+
+    let normalized = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(parser.error(&format!("unexpected `{}`", token)));
+    }
+
+    let mut ids = parser.ids;
+    ids.sort();
+    ids.dedup();
+    let mut refs = parser.refs;
+    refs.sort();
+    refs.dedup();
+
+    Ok(License {
+        expression: normalized,
+        ids,
+        refs,
+    })
+}
+
+fn load_list(list: &'static str) -> HashMap<String, &'static str> {
+    list.lines().map(|id| (id.to_lowercase(), id)).collect()
+}
+
+fn tokenize(expression: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in expression.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = start.take() {
+                tokens.push(&expression[start..i]);
+            }
+            if c == '(' || c == ')' {
+                tokens.push(&expression[i..=i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+
+    if let Some(start) = start {
+        tokens.push(&expression[start..]);
+    }
+
+    tokens
+}
+
+fn is_operator(token: &str) -> bool {
+    ["AND", "OR", "WITH"]
+        .iter()
+        .any(|op| token.eq_ignore_ascii_case(op))
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<&'a str>,
+    pos: usize,
+    ids: Vec<String>,
+    refs: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat_operator(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some(token) if token.eq_ignore_ascii_case(op) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<String, Error> {
+        let mut expression = self.parse_and()?;
+        while self.eat_operator("OR") {
+            expression = format!("{} OR {}", expression, self.parse_and()?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<String, Error> {
+        let mut expression = self.parse_with()?;
+        while self.eat_operator("AND") {
+            expression = format!("{} AND {}", expression, self.parse_with()?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_with(&mut self) -> Result<String, Error> {
+        let license = self.parse_primary()?;
+        if !self.eat_operator("WITH") {
+            return Ok(license);
+        }
+
+        let exception = match self.next() {
+            Some(token) => token,
+            None => return Err(self.error("expected an exception after `WITH`")),
+        };
+        match EXCEPTIONS.get(&exception.to_lowercase()) {
+            Some(exception) => Ok(format!("{} WITH {}", license, exception)),
+            None => Err(human!(
+                Reason::InvalidManifest,
+                "Unknown license exception `{}` in license `{}`",
+                exception,
+                self.source
+            )),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Some("(") => {
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(format!("({})", expression)),
+                    _ => Err(self.error("unclosed parenthesis")),
+                }
+            }
+            Some(token) if token == ")" || is_operator(token) => {
+                Err(self.error(&format!("unexpected `{}`", token)))
+            }
+            Some(token) => self.parse_license_id(token),
+            None => Err(self.error("expected a license identifier")),
+        }
+    }
+
+    fn parse_license_id(&mut self, token: &str) -> Result<String, Error> {
+        // user defined licenses are opaque to the registry
+        if is_license_ref(token) {
+            self.refs.push(token.to_owned());
+            return Ok(token.to_owned());
+        }
+
+        let (id, suffix) = if token.ends_with('+') {
+            (&token[..token.len() - 1], "+")
+        } else {
+            (token, "")
+        };
+
+        match canonical_id(id) {
+            Some(id) => {
+                self.ids.push(id.to_owned());
+                Ok(format!("{}{}", id, suffix))
+            }
+            None => Err(human!(
+                Reason::InvalidManifest,
+                "Unknown license `{}` in license `{}`, expected an SPDX license identifier",
+                id,
+                self.source
+            )),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        human!(
+            Reason::InvalidManifest,
+            "Invalid license expression `{}`: {}",
+            self.source,
+            message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(expression: &str) -> String {
+        parse_license(expression).unwrap().expression
+    }
+
+    fn parser(expression: &str) -> Parser {
+        Parser {
+            source: expression,
+            tokens: tokenize(expression),
+            pos: 0,
+            ids: Vec::new(),
+            refs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn normalizes_case() {
+        let license = parse_license("mit or apache-2.0").unwrap();
+        assert_eq!(license.expression, "MIT OR Apache-2.0");
+        assert_eq!(license.ids, vec!["Apache-2.0", "MIT"]);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // an AND term ends at the next OR
+        let mut and_first = parser("MIT AND Apache-2.0 OR Zlib");
+        assert_eq!(and_first.parse_and().unwrap(), "MIT AND Apache-2.0");
+        assert_eq!(and_first.peek(), Some("OR"));
+
+        // but takes in everything up to it
+        let mut or_first = parser("MIT OR Apache-2.0 AND Zlib");
+        assert_eq!(or_first.parse_and().unwrap(), "MIT");
+        assert_eq!(or_first.peek(), Some("OR"));
+
+        // and WITH binds tighter than AND
+        let mut with = parser("GPL-2.0-or-later WITH Classpath-exception-2.0 AND MIT");
+        assert_eq!(
+            with.parse_with().unwrap(),
+            "GPL-2.0-or-later WITH Classpath-exception-2.0"
+        );
+        assert_eq!(with.peek(), Some("AND"));
+
+        assert_eq!(
+            normalize("MIT or Apache-2.0 and BSD-3-Clause"),
+            "MIT OR Apache-2.0 AND BSD-3-Clause"
+        );
+    }
+
+    #[test]
+    fn dangling_operators() {
+        assert!(parse_license("MIT AND").is_err());
+        assert!(parse_license("OR MIT").is_err());
+        assert!(parse_license("MIT OR OR Apache-2.0").is_err());
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            normalize("(mit OR apache-2.0) AND Zlib"),
+            "(MIT OR Apache-2.0) AND Zlib"
+        );
+        assert_eq!(normalize("((MIT))"), "((MIT))");
+        assert!(parse_license("(MIT OR Apache-2.0").is_err());
+        assert!(parse_license("MIT)").is_err());
+        assert!(parse_license("()").is_err());
+        assert!(parse_license(")(").is_err());
+    }
+
+    #[test]
+    fn with_exceptions() {
+        assert_eq!(
+            normalize("gpl-2.0-or-later with classpath-exception-2.0"),
+            "GPL-2.0-or-later WITH Classpath-exception-2.0"
+        );
+        assert!(parse_license("GPL-2.0-or-later WITH").is_err());
+        assert!(parse_license("GPL-2.0-or-later WITH Not-An-Exception").is_err());
+    }
+
+    #[test]
+    fn or_later_suffix() {
+        let license = parse_license("gpl-2.0+").unwrap();
+        assert_eq!(license.expression, "GPL-2.0+");
+        assert_eq!(license.ids, vec!["GPL-2.0"]);
+    }
+
+    #[test]
+    fn unknown_identifiers() {
+        assert!(parse_license("Not-A-License").is_err());
+        assert!(parse_license("MIT OR Not-A-License").is_err());
+        assert!(parse_license("").is_err());
+
+        let license = parse_license("LicenseRef-Proprietary").unwrap();
+        assert_eq!(license.expression, "LicenseRef-Proprietary");
+        assert!(license.ids.is_empty());
+        assert_eq!(license.refs, vec!["LicenseRef-Proprietary"]);
+    }
+
+    #[test]
+    fn rejects_comma_lists() {
+        let err = parse_license("MIT, Apache-2.0").unwrap_err();
+        assert!(err.to_string().contains("`OR` or `AND`"));
+        assert!(parse_license("MIT,").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Component, Path};
+
+use ammonia::Builder;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use url::Url;
+
+/// Renders a readme to sanitized html.
+///
+/// Relative links and images are resolved against the directory of the
+/// readme in the repository of the package. This is only done for GitHub
+/// repositories, since other hosts lay out their file urls differently.
+pub fn render_readme(markdown: &str, repository: Option<&str>, readme_path: &Path) -> String {
+    let base = repository.and_then(github_base);
+    let readme_dir = readme_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let parser = Parser::new_ext(markdown, Options::all()).map(|event| match event {
+        Event::Start(Tag::Link(link_type, dest, title)) => Event::Start(Tag::Link(
+            link_type,
+            resolve_relative(base.as_ref(), "blob", readme_dir, dest),
+            title,
+        )),
+        Event::Start(Tag::Image(link_type, dest, title)) => Event::Start(Tag::Image(
+            link_type,
+            resolve_relative(base.as_ref(), "raw", readme_dir, dest),
+            title,
+        )),
+        event => event,
+    });
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+
+    Builder::default()
+        .link_rel(Some("nofollow noopener noreferrer"))
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+/// The url of a GitHub repository, or `None` for any other host.
+fn github_base(repository: &str) -> Option<Url> {
+    let url = Url::parse(&format!("{}/", repository.trim_end_matches('/'))).ok()?;
+
+    match url.host_str() {
+        Some("github.com") | Some("www.github.com") => Some(url),
+        _ => None,
+    }
+}
+
+fn resolve_relative<'a>(
+    base: Option<&Url>,
+    kind: &str,
+    readme_dir: &Path,
+    dest: CowStr<'a>,
+) -> CowStr<'a> {
+    let base = match base {
+        Some(base) => base,
+        None => return dest,
+    };
+
+    if dest.starts_with('#') || Url::parse(&dest).is_ok() {
+        return dest;
+    }
+
+    // the query and fragment are kept as they are
+    let split = dest.find(|c: char| c == '?' || c == '#').unwrap_or_else(|| dest.len());
+    let (path, suffix) = dest.split_at(split);
+
+    // absolute paths start at the repository root
+    let mut segments: Vec<String> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        readme_dir
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            }).collect()
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            // never climbs out of the repository
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment.to_owned()),
+        }
+    }
+
+    match base.join(&format!("{}/HEAD/{}{}", kind, segments.join("/"), suffix)) {
+        Ok(url) => url.into_string().into(),
+        Err(_) => dest,
+    }
+}
@@ -1,6 +1,8 @@
 #[macro_use]
 pub mod error;
 pub mod async_await;
+pub mod license;
+pub mod markdown;
 pub mod rfc3339;
 
 use actix_web::HttpResponse;